@@ -1,6 +1,5 @@
-use std::collections::HashMap;
-
 use lazy_static::lazy_static;
+use sequencer::config::BlockContextConfig;
 use starknet::core::types::contract::legacy::LegacyContractClass;
 use starknet_api::{
     contract_address,
@@ -19,21 +18,11 @@ lazy_static! {
     // Chain params
     pub static ref CHAIN_ID: u64 = 0x4b4b5254;
 
-    // Vm resources: maps resource name to fee cost.
-    pub static ref VM_RESOURCES: HashMap<String, f64> = [
-        (String::from("n_steps"), 1_f64),
-        ("pedersen_builtin".to_string(), 1_f64),
-        ("range_check_builtin".to_string(), 1_f64),
-        ("ecdsa_builtin".to_string(), 1_f64),
-        ("bitwise_builtin".to_string(), 1_f64),
-        ("poseidon_builtin".to_string(), 1_f64),
-        ("output_builtin".to_string(), 1_f64),
-        ("ec_op_builtin".to_string(), 1_f64),
-        ("keccak_builtin".to_string(), 1_f64),
-        ("segment_arena_builtin".to_string(), 1_f64),
-    ]
-    .into_iter()
-    .collect();
+    // Block context config: step limits, gas prices and VM resources costs.
+    // Loaded from the file at SEQUENCER_CONFIG if set, defaults otherwise.
+    pub static ref BLOCK_CONTEXT_CONFIG: BlockContextConfig = std::env::var("SEQUENCER_CONFIG")
+        .map(|path| BlockContextConfig::from_file(path).expect("Failed to load the sequencer config"))
+        .unwrap_or_default();
 
     // Main addresses
     pub static ref ETH_FEE_TOKEN_ADDRESS: ContractAddress = contract_address!("0x049D36570D4e46f48e99674bd3fcc84644DdD6b96F7C741B1562B82f9e004dC7");
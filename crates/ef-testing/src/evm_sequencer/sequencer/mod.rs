@@ -3,12 +3,9 @@ pub mod v0;
 #[cfg(feature = "v1")]
 pub mod v1;
 
-use std::{
-    ops::{Deref, DerefMut},
-    sync::Arc,
-};
+use std::ops::{Deref, DerefMut};
 
-use blockifier::block_context::{BlockContext, FeeTokenAddresses, GasPrices};
+use blockifier::block_context::FeeTokenAddresses;
 use reth_primitives::Address;
 use sequencer::{sequencer::Sequencer, state::State};
 use starknet_api::{
@@ -17,7 +14,7 @@ use starknet_api::{
 };
 
 use super::{
-    constants::{CHAIN_ID, ETH_FEE_TOKEN_ADDRESS, STRK_FEE_TOKEN_ADDRESS, VM_RESOURCES},
+    constants::{BLOCK_CONTEXT_CONFIG, CHAIN_ID, ETH_FEE_TOKEN_ADDRESS, STRK_FEE_TOKEN_ADDRESS},
     utils::compute_starknet_address,
 };
 
@@ -42,26 +39,27 @@ impl KakarotSequencer {
             }
         };
 
-        let block_context = BlockContext {
-            chain_id: ChainId(String::from_utf8(CHAIN_ID.to_be_bytes().to_vec()).unwrap()),
-            block_number: BlockNumber(block_number),
-            block_timestamp: BlockTimestamp(block_timestamp),
-            sequencer_address: compute_starknet_address(&coinbase_address)
-                .try_into()
-                .expect("Failed to convert coinbase address to contract address"),
-            fee_token_addresses: FeeTokenAddresses {
+        // The chain id, the addresses and the block must match the initial
+        // state and the test case, only the limits and the fees are taken
+        // from the config.
+        let block_context = BLOCK_CONTEXT_CONFIG
+            .clone()
+            .chain_id(ChainId(
+                String::from_utf8(CHAIN_ID.to_be_bytes().to_vec()).unwrap(),
+            ))
+            .block_number(BlockNumber(block_number))
+            .block_timestamp(BlockTimestamp(block_timestamp))
+            .sequencer_address(
+                compute_starknet_address(&coinbase_address)
+                    .try_into()
+                    .expect("Failed to convert coinbase address to contract address"),
+            )
+            .fee_token_addresses(FeeTokenAddresses {
                 eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
                 strk_fee_token_address: *STRK_FEE_TOKEN_ADDRESS,
-            },
-            vm_resource_fee_cost: Arc::new(VM_RESOURCES.clone()),
-            gas_prices: GasPrices {
-                eth_l1_gas_price: 1,
-                strk_l1_gas_price: 1,
-            },
-            invoke_tx_max_n_steps: 50_000_000,
-            validate_max_n_steps: 50_000_000,
-            max_recursion_depth: 8192,
-        };
+            })
+            .build()
+            .expect("Invalid sequencer config");
         let sequencer = Sequencer::new(block_context, initial_state, coinbase_address);
         Self(sequencer)
     }
@@ -0,0 +1,565 @@
+use std::sync::Arc;
+
+use blockifier::{
+    execution::contract_class::ContractClass,
+    state::{errors::StateError, state_api::StateReader},
+    transaction::{
+        account_transaction::AccountTransaction,
+        errors::TransactionExecutionError,
+        transaction_execution::Transaction,
+        transactions::{DeclareTransaction, DeployAccountTransaction, InvokeTransaction},
+    },
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use starknet::core::{types::FieldElement, utils::get_contract_address};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    transaction::{
+        Calldata, ContractAddressSalt, DeclareTransaction as ApiDeclareTransaction,
+        DeclareTransactionV0V1, DeclareTransactionV2,
+        DeployAccountTransaction as ApiDeployAccountTransaction, Fee,
+        InvokeTransaction as ApiInvokeTransaction, InvokeTransactionV1, TransactionHash,
+        TransactionSignature, TransactionVersion,
+    },
+    StarknetApiError,
+};
+use starknet_crypto::{get_public_key, rfc6979_generate_k, sign, SignError};
+use thiserror::Error;
+
+use crate::{
+    sequencer::Sequencer,
+    transaction::{
+        compute_declare_transaction_hash, compute_deploy_account_transaction_hash,
+        compute_transaction_hash,
+    },
+};
+
+#[derive(Error, Debug)]
+pub enum AccountError {
+    #[error(transparent)]
+    SignError(#[from] SignError),
+    #[error(transparent)]
+    StateError(#[from] StateError),
+    #[error(transparent)]
+    StarknetApiError(#[from] StarknetApiError),
+    #[error(transparent)]
+    TransactionExecutionError(#[from] TransactionExecutionError),
+}
+
+/// STARK private key, along with its public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SigningKey {
+    secret_scalar: FieldElement,
+    public_key: FieldElement,
+}
+
+impl SigningKey {
+    /// Creates the key from its secret scalar, which must be lower than the
+    /// order of the STARK curve.
+    pub fn from_secret_scalar(secret_scalar: FieldElement) -> Self {
+        Self {
+            secret_scalar,
+            public_key: get_public_key(&secret_scalar),
+        }
+    }
+
+    /// Derives the key from the seed: the same seed always derives the same
+    /// key.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut counter = 0u32;
+        loop {
+            let mut hasher = Sha256::new();
+            hasher.update(seed);
+            hasher.update(counter.to_be_bytes());
+            if let Some(secret_scalar) = to_secret_scalar(hasher.finalize().into()) {
+                return Self::from_secret_scalar(secret_scalar);
+            }
+            counter += 1;
+        }
+    }
+
+    /// Generates a random key.
+    pub fn random() -> Self {
+        loop {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            if let Some(secret_scalar) = to_secret_scalar(bytes) {
+                return Self::from_secret_scalar(secret_scalar);
+            }
+        }
+    }
+
+    pub const fn secret_scalar(&self) -> FieldElement {
+        self.secret_scalar
+    }
+
+    pub const fn public_key(&self) -> FieldElement {
+        self.public_key
+    }
+
+    /// Signs the hash, using a deterministic nonce (RFC 6979). Returns the
+    /// signature as `[r, s]`.
+    ///
+    /// # Errors
+    ///
+    /// If the hash is out of range.
+    pub fn sign(&self, hash: FieldElement) -> Result<[FieldElement; 2], SignError> {
+        let k = rfc6979_generate_k(&hash, &self.secret_scalar, None);
+        let signature = sign(&self.secret_scalar, &hash, &k)?;
+        Ok([signature.r, signature.s])
+    }
+}
+
+/// Masks the bytes to 250 bits, which is always lower than the order of the
+/// STARK curve. Returns `None` for the zero scalar.
+fn to_secret_scalar(mut bytes: [u8; 32]) -> Option<FieldElement> {
+    bytes[0] &= 0x03;
+    FieldElement::from_bytes_be(&bytes)
+        .ok()
+        .filter(|scalar| *scalar != FieldElement::ZERO)
+}
+
+/// Account signing the transactions it sends, for accounts validating a STARK
+/// signature over the transaction hash (e.g. OpenZeppelin accounts). The
+/// nonce is incremented each time a transaction is built: if a transaction
+/// is rejected, the nonce should be resynchronized with `sync_nonce`.
+#[derive(Clone, Debug)]
+pub struct TestAccount {
+    address: ContractAddress,
+    signing_key: SigningKey,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+}
+
+impl TestAccount {
+    /// Creates an account for an already deployed contract, starting at nonce
+    /// zero.
+    pub const fn new(
+        address: ContractAddress,
+        signing_key: SigningKey,
+        chain_id: FieldElement,
+    ) -> Self {
+        Self {
+            address,
+            signing_key,
+            chain_id,
+            nonce: FieldElement::ZERO,
+        }
+    }
+
+    /// Creates the deploy account transaction of a new account of the class,
+    /// with the public key of the signing key as the constructor calldata.
+    /// Returns the account, whose nonce is already incremented, and the
+    /// transaction.
+    ///
+    /// # Errors
+    ///
+    /// If the address is invalid or the signature fails.
+    pub fn deploy(
+        signing_key: SigningKey,
+        class_hash: ClassHash,
+        salt: FieldElement,
+        chain_id: FieldElement,
+        max_fee: Fee,
+    ) -> Result<(Self, Transaction), AccountError> {
+        let constructor_calldata = vec![signing_key.public_key()];
+        Self::deploy_with_calldata(
+            signing_key,
+            class_hash,
+            salt,
+            constructor_calldata,
+            chain_id,
+            max_fee,
+        )
+    }
+
+    /// Creates the deploy account transaction of a new account of the class,
+    /// with the provided constructor calldata.
+    ///
+    /// # Errors
+    ///
+    /// If the address is invalid or the signature fails.
+    pub fn deploy_with_calldata(
+        signing_key: SigningKey,
+        class_hash: ClassHash,
+        salt: FieldElement,
+        constructor_calldata: Vec<FieldElement>,
+        chain_id: FieldElement,
+        max_fee: Fee,
+    ) -> Result<(Self, Transaction), AccountError> {
+        let class_hash_felt = to_field_element(&class_hash.0);
+        let address = get_contract_address(
+            salt,
+            class_hash_felt,
+            &constructor_calldata,
+            FieldElement::ZERO,
+        );
+        let tx_hash = compute_deploy_account_transaction_hash(
+            address,
+            class_hash_felt,
+            salt,
+            &constructor_calldata,
+            FieldElement::from(max_fee.0),
+            chain_id,
+            FieldElement::ZERO,
+        );
+        let contract_address = ContractAddress(PatriciaKey::try_from(StarkFelt::from(address))?);
+
+        let mut account = Self::new(contract_address, signing_key, chain_id);
+        let tx = ApiDeployAccountTransaction {
+            max_fee,
+            version: TransactionVersion(StarkFelt::from(1u8)),
+            signature: account.sign(tx_hash)?,
+            nonce: account.next_nonce(),
+            class_hash,
+            contract_address_salt: ContractAddressSalt(salt.into()),
+            constructor_calldata: to_calldata(&constructor_calldata),
+        };
+
+        let transaction = Transaction::AccountTransaction(AccountTransaction::DeployAccount(
+            DeployAccountTransaction {
+                tx,
+                tx_hash: TransactionHash(tx_hash.into()),
+                contract_address,
+                only_query: false,
+            },
+        ));
+        Ok((account, transaction))
+    }
+
+    pub const fn address(&self) -> ContractAddress {
+        self.address
+    }
+
+    pub const fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    /// Returns the nonce of the next transaction.
+    pub fn nonce(&self) -> Nonce {
+        Nonce(self.nonce.into())
+    }
+
+    pub fn set_nonce(&mut self, nonce: Nonce) {
+        self.nonce = to_field_element(&nonce.0);
+    }
+
+    /// Sets the nonce to the nonce of the account in the sequencer state.
+    ///
+    /// # Errors
+    ///
+    /// If the nonce fails to be read.
+    pub fn sync_nonce<S, A>(&mut self, sequencer: &mut Sequencer<S, A>) -> Result<(), AccountError>
+    where
+        for<'any> &'any mut S: blockifier::state::state_api::State + StateReader,
+    {
+        let nonce = (&mut sequencer.state).get_nonce_at(self.address)?;
+        self.set_nonce(nonce);
+        Ok(())
+    }
+
+    /// Creates a signed invoke transaction with the calldata of the account
+    /// `__execute__` entry point.
+    ///
+    /// # Errors
+    ///
+    /// If the signature fails.
+    pub fn invoke(
+        &mut self,
+        calldata: Vec<FieldElement>,
+        max_fee: Fee,
+    ) -> Result<Transaction, AccountError> {
+        let sender_address = to_field_element(self.address.0.key());
+        let tx_hash = compute_transaction_hash(
+            sender_address,
+            &calldata,
+            FieldElement::from(max_fee.0),
+            self.chain_id,
+            self.nonce,
+        );
+
+        let tx = ApiInvokeTransaction::V1(InvokeTransactionV1 {
+            max_fee,
+            signature: self.sign(tx_hash)?,
+            nonce: self.next_nonce(),
+            sender_address: self.address,
+            calldata: to_calldata(&calldata),
+        });
+
+        Ok(Transaction::AccountTransaction(AccountTransaction::Invoke(
+            InvokeTransaction {
+                tx,
+                tx_hash: TransactionHash(tx_hash.into()),
+                only_query: false,
+            },
+        )))
+    }
+
+    /// Creates a signed declare transaction for the class. Cairo 0 classes
+    /// are declared with a V1 transaction, Cairo 1 classes with a V2
+    /// transaction, for which the compiled class hash is required.
+    ///
+    /// # Errors
+    ///
+    /// If the signature fails or if the transaction doesn't match the class.
+    pub fn declare(
+        &mut self,
+        class_hash: ClassHash,
+        contract_class: ContractClass,
+        compiled_class_hash: Option<CompiledClassHash>,
+        max_fee: Fee,
+    ) -> Result<Transaction, AccountError> {
+        let tx_hash = compute_declare_transaction_hash(
+            to_field_element(self.address.0.key()),
+            to_field_element(&class_hash.0),
+            FieldElement::from(max_fee.0),
+            self.chain_id,
+            self.nonce,
+            compiled_class_hash.map(|hash| to_field_element(&hash.0)),
+        );
+        let signature = self.sign(tx_hash)?;
+        let nonce = self.next_nonce();
+
+        let tx = match compiled_class_hash {
+            None => ApiDeclareTransaction::V1(DeclareTransactionV0V1 {
+                max_fee,
+                signature,
+                nonce,
+                class_hash,
+                sender_address: self.address,
+            }),
+            Some(compiled_class_hash) => ApiDeclareTransaction::V2(DeclareTransactionV2 {
+                max_fee,
+                signature,
+                nonce,
+                class_hash,
+                compiled_class_hash,
+                sender_address: self.address,
+            }),
+        };
+
+        Ok(Transaction::AccountTransaction(
+            AccountTransaction::Declare(DeclareTransaction::new(
+                tx,
+                TransactionHash(tx_hash.into()),
+                contract_class,
+            )?),
+        ))
+    }
+
+    fn sign(&self, hash: FieldElement) -> Result<TransactionSignature, SignError> {
+        let signature = self.signing_key.sign(hash)?;
+        Ok(TransactionSignature(
+            signature.into_iter().map(StarkFelt::from).collect(),
+        ))
+    }
+
+    /// Returns the current nonce and increments it.
+    fn next_nonce(&mut self) -> Nonce {
+        let nonce = self.nonce();
+        self.nonce += FieldElement::ONE;
+        nonce
+    }
+}
+
+fn to_field_element(felt: &StarkFelt) -> FieldElement {
+    FieldElement::from_bytes_be(felt.bytes()).expect("a StarkFelt is a valid field element")
+}
+
+fn to_calldata(calldata: &[FieldElement]) -> Calldata {
+    Calldata(Arc::new(
+        calldata.iter().copied().map(StarkFelt::from).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{abi::abi_utils::get_storage_var_address, state::state_api::State as _};
+    use starknet::macros::selector;
+    use starknet_crypto::verify;
+
+    use crate::{
+        constants::test_constants::{
+            block_context, read_contract_class, ONE_CLASS_HASH, TEST_ACCOUNT, TEST_CONTRACT,
+            TWO_CLASS_HASH,
+        },
+        execution::Execution,
+        state::State,
+    };
+
+    use super::*;
+
+    fn chain_id() -> FieldElement {
+        FieldElement::from_byte_slice_be(b"KKRT").unwrap()
+    }
+
+    fn signature_of(transaction: &Transaction) -> (TransactionHash, Vec<StarkFelt>) {
+        match transaction {
+            Transaction::AccountTransaction(AccountTransaction::Invoke(tx)) => {
+                let ApiInvokeTransaction::V1(inner) = &tx.tx else {
+                    panic!("expected an invoke v1");
+                };
+                (tx.tx_hash, inner.signature.0.clone())
+            }
+            Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+                (tx.tx_hash, tx.tx.signature.0.clone())
+            }
+            _ => panic!("unexpected transaction"),
+        }
+    }
+
+    fn assert_signed_by(transaction: &Transaction, signing_key: &SigningKey) {
+        let (tx_hash, signature) = signature_of(transaction);
+        assert_eq!(signature.len(), 2);
+        assert!(verify(
+            &signing_key.public_key(),
+            &to_field_element(&tx_hash.0),
+            &to_field_element(&signature[0]),
+            &to_field_element(&signature[1]),
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_signing_key_from_seed() {
+        // When
+        let key = SigningKey::from_seed(b"account 0");
+
+        // Then
+        assert_eq!(key, SigningKey::from_seed(b"account 0"));
+        assert_ne!(key, SigningKey::from_seed(b"account 1"));
+        assert_eq!(key.public_key(), get_public_key(&key.secret_scalar()));
+        assert_ne!(SigningKey::random(), SigningKey::random());
+    }
+
+    #[test]
+    fn test_deploy_and_invoke() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), ());
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        (&mut sequencer.state)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/account.json"),
+            )
+            .unwrap();
+        let signing_key = SigningKey::from_seed(b"account");
+        let calldata = vec![
+            to_field_element(TEST_CONTRACT.0.key()),
+            selector!("inc"),
+            FieldElement::ZERO,
+        ];
+
+        // When
+        let (mut account, deploy) = TestAccount::deploy_with_calldata(
+            signing_key.clone(),
+            *TWO_CLASS_HASH,
+            FieldElement::ONE,
+            vec![],
+            chain_id(),
+            Fee(1_000_000),
+        )
+        .unwrap();
+        assert_signed_by(&deploy, &signing_key);
+        sequencer.execute(deploy).unwrap();
+
+        let invoke = account.invoke(calldata.clone(), Fee(1_000_000)).unwrap();
+        assert_signed_by(&invoke, &signing_key);
+        sequencer.execute(invoke).unwrap();
+        let invoke = account.invoke(calldata, Fee(1_000_000)).unwrap();
+        sequencer.execute(invoke).unwrap();
+
+        // Then
+        assert_eq!(account.nonce(), Nonce(StarkFelt::from(3u8)));
+        let nonce = (&mut sequencer.state)
+            .get_nonce_at(account.address())
+            .unwrap();
+        assert_eq!(nonce, account.nonce());
+        let counter = (&mut sequencer.state)
+            .get_storage_at(*TEST_CONTRACT, get_storage_var_address("counter", &[]))
+            .unwrap();
+        assert_eq!(counter, StarkFelt::from(2u8));
+    }
+
+    #[test]
+    fn test_invoke_validating_account() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), ());
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_1/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        sequencer
+            .deploy_class_at(
+                *TEST_ACCOUNT,
+                *TWO_CLASS_HASH,
+                read_contract_class(
+                    "src/test_data/cairo_1/compiled_classes/validating_account.json",
+                ),
+            )
+            .unwrap();
+        let signing_key = SigningKey::from_seed(b"account");
+        (&mut sequencer.state).set_storage_at(
+            *TEST_ACCOUNT,
+            get_storage_var_address("public_key", &[]),
+            signing_key.public_key().into(),
+        );
+        // The account executes an array of calls.
+        let calldata = vec![
+            FieldElement::ONE,
+            to_field_element(TEST_CONTRACT.0.key()),
+            selector!("inc"),
+            FieldElement::ZERO,
+        ];
+        let mut account = TestAccount::new(*TEST_ACCOUNT, signing_key, chain_id());
+        let mut impostor = TestAccount::new(
+            *TEST_ACCOUNT,
+            SigningKey::from_seed(b"impostor"),
+            chain_id(),
+        );
+
+        // When
+        let invoke = impostor.invoke(calldata.clone(), Fee(1_000_000)).unwrap();
+        let rejected = sequencer.execute(invoke);
+        let invoke = account.invoke(calldata, Fee(1_000_000)).unwrap();
+        let info = sequencer.execute(invoke).unwrap();
+
+        // Then
+        assert!(matches!(
+            rejected,
+            Err(TransactionExecutionError::ValidateTransactionError(_))
+        ));
+        assert!(info.revert_error.is_none());
+        let nonce = (&mut sequencer.state).get_nonce_at(*TEST_ACCOUNT).unwrap();
+        assert_eq!(nonce, Nonce(StarkFelt::from(1u8)));
+        let counter = (&mut sequencer.state)
+            .get_storage_at(*TEST_CONTRACT, get_storage_var_address("counter", &[]))
+            .unwrap();
+        assert_eq!(counter, StarkFelt::from(1u8));
+    }
+
+    #[test]
+    fn test_sync_nonce() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), ());
+        let mut account = TestAccount::new(*TEST_CONTRACT, SigningKey::from_seed(b"a"), chain_id());
+        sequencer.set_nonce(*TEST_CONTRACT, Nonce(StarkFelt::from(5u8)));
+
+        // When
+        account.sync_nonce(&mut sequencer).unwrap();
+
+        // Then
+        assert_eq!(account.nonce(), Nonce(StarkFelt::from(5u8)));
+    }
+}
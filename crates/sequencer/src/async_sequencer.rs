@@ -0,0 +1,308 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    thread::JoinHandle,
+};
+
+use blockifier::{
+    state::state_api::{State, StateReader},
+    transaction::{errors::TransactionExecutionError, transaction_execution::Transaction},
+};
+use starknet::core::types::TransactionReceipt;
+use starknet_api::block::BlockHash;
+use thiserror::Error;
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+
+use crate::{
+    block_context::BlockContextError,
+    commit::Committer,
+    execution::Execution,
+    receipt::{to_transaction_receipt, transaction_hash, TransactionKind},
+    sequencer::Sequencer,
+};
+
+#[derive(Error, Debug)]
+pub enum AsyncSequencerError {
+    #[error(transparent)]
+    TransactionExecutionError(#[from] TransactionExecutionError),
+    #[error(transparent)]
+    BlockContextError(#[from] BlockContextError),
+    #[error("the sequencer queue is full")]
+    QueueFull,
+    #[error("the sequencer worker stopped")]
+    WorkerStopped,
+}
+
+type ExecutionResponse = Result<TransactionReceipt, TransactionExecutionError>;
+
+enum Request {
+    Execute {
+        transaction: Transaction,
+        respond: oneshot::Sender<ExecutionResponse>,
+    },
+    MineBlock {
+        respond: oneshot::Sender<Result<(), BlockContextError>>,
+    },
+}
+
+/// Asynchronous front-end of a sequencer. Transactions and block closures
+/// are sent over a bounded channel to a worker thread, which owns the
+/// sequencer and handles them in order, so that the CPU-bound execution
+/// never blocks the async runtime. A block mined with
+/// [`AsyncSequencer::mine_block`] contains all the transactions queued
+/// before it.
+///
+/// Backpressure: once `capacity` transactions are queued,
+/// [`AsyncSequencer::execute`] waits for room in the queue and
+/// [`AsyncSequencer::try_execute`] fails with
+/// [`AsyncSequencerError::QueueFull`].
+///
+/// Cancellation: dropping the future of a transaction before the worker
+/// picks it up cancels the transaction, which is then skipped. A transaction
+/// already being executed is committed nonetheless.
+///
+/// Cloning the handle doesn't clone the sequencer. The worker stops once all
+/// the handles are dropped, and returns the sequencer.
+#[derive(Clone, Debug)]
+pub struct AsyncSequencer {
+    sender: mpsc::Sender<Request>,
+}
+
+impl AsyncSequencer {
+    /// Spawns the worker thread owning the sequencer, with a queue of at
+    /// most `capacity` pending transactions. The join handle of the worker
+    /// returns the sequencer once all the handles are dropped.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity is zero.
+    pub fn spawn<S, A>(
+        sequencer: Sequencer<S, A>,
+        capacity: usize,
+    ) -> (Self, JoinHandle<Sequencer<S, A>>)
+    where
+        S: Send + 'static,
+        A: Send + 'static,
+        for<'any> &'any mut S: State + StateReader + Committer<S>,
+    {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let worker = std::thread::spawn(move || run_worker(sequencer, receiver));
+        (Self { sender }, worker)
+    }
+
+    /// Queues the transaction, waiting for room in the queue if it is full,
+    /// and resolves to its receipt once executed. Reverted transactions
+    /// resolve to a receipt with a reverted execution result.
+    ///
+    /// # Errors
+    ///
+    /// If the transaction is rejected or if the worker stopped.
+    pub async fn execute(
+        &self,
+        transaction: Transaction,
+    ) -> Result<TransactionReceipt, AsyncSequencerError> {
+        let (respond, receiver) = oneshot::channel();
+        self.sender
+            .send(Request::Execute {
+                transaction,
+                respond,
+            })
+            .await
+            .map_err(|_| AsyncSequencerError::WorkerStopped)?;
+        PendingReceipt { receiver }.await
+    }
+
+    /// Queues the transaction without waiting, and returns the future of its
+    /// receipt.
+    ///
+    /// # Errors
+    ///
+    /// If the queue is full or if the worker stopped.
+    pub fn try_execute(
+        &self,
+        transaction: Transaction,
+    ) -> Result<PendingReceipt, AsyncSequencerError> {
+        let (respond, receiver) = oneshot::channel();
+        self.sender
+            .try_send(Request::Execute {
+                transaction,
+                respond,
+            })
+            .map_err(|error| match error {
+                TrySendError::Full(_) => AsyncSequencerError::QueueFull,
+                TrySendError::Closed(_) => AsyncSequencerError::WorkerStopped,
+            })?;
+        Ok(PendingReceipt { receiver })
+    }
+
+    /// Closes the current block once the transactions queued before are
+    /// executed, waiting for room in the queue if it is full.
+    ///
+    /// # Errors
+    ///
+    /// If the block number overflows, if the next block timestamp is invalid
+    /// or if the worker stopped.
+    pub async fn mine_block(&self) -> Result<(), AsyncSequencerError> {
+        let (respond, receiver) = oneshot::channel();
+        self.sender
+            .send(Request::MineBlock { respond })
+            .await
+            .map_err(|_| AsyncSequencerError::WorkerStopped)?;
+        receiver
+            .await
+            .map_err(|_| AsyncSequencerError::WorkerStopped)?
+            .map_err(Into::into)
+    }
+}
+
+/// Future of the receipt of a queued transaction. Dropping it before the
+/// transaction is executed cancels the transaction.
+#[derive(Debug)]
+pub struct PendingReceipt {
+    receiver: oneshot::Receiver<ExecutionResponse>,
+}
+
+impl Future for PendingReceipt {
+    type Output = Result<TransactionReceipt, AsyncSequencerError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|response| match response {
+                Ok(response) => response.map_err(Into::into),
+                Err(_) => Err(AsyncSequencerError::WorkerStopped),
+            })
+    }
+}
+
+/// Handles the queued requests until all the senders are dropped. Blocks
+/// aren't hashed by the sequencer, receipts use the default block hash.
+fn run_worker<S, A>(
+    mut sequencer: Sequencer<S, A>,
+    mut receiver: mpsc::Receiver<Request>,
+) -> Sequencer<S, A>
+where
+    for<'any> &'any mut S: State + StateReader + Committer<S>,
+{
+    while let Some(request) = receiver.blocking_recv() {
+        match request {
+            Request::Execute {
+                transaction,
+                respond,
+            } => {
+                if respond.is_closed() {
+                    continue;
+                }
+
+                let transaction_hash = transaction_hash(&transaction);
+                let kind = TransactionKind::from(&transaction);
+                let block_number = sequencer.block_context().block_number;
+                let response = sequencer.execute(transaction).map(|info| {
+                    to_transaction_receipt(
+                        transaction_hash,
+                        kind,
+                        &info,
+                        BlockHash::default(),
+                        block_number,
+                    )
+                });
+                // The caller may have given up on the receipt during the
+                // execution.
+                let _ = respond.send(response);
+            }
+            // The block is mined even if the caller gave up, as the
+            // transactions queued after it expect a new block.
+            Request::MineBlock { respond } => {
+                let _ = respond.send(sequencer.mine_block());
+            }
+        }
+    }
+    sequencer
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::abi::abi_utils::get_storage_var_address;
+    use starknet::core::types::{ExecutionResult, InvokeTransactionReceipt};
+    use starknet_api::hash::StarkFelt;
+
+    use crate::constants::test_constants::{
+        counter_sequencer, increment, ONE_BLOCK_NUMBER, TEST_CONTRACT,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_sequencer() {
+        // Given
+        let (handle, worker) = AsyncSequencer::spawn(counter_sequencer(), 4);
+
+        // When
+        let first = handle.try_execute(increment(0)).unwrap();
+        let second = handle.execute(increment(1));
+        let (first, second) = tokio::join!(first, second);
+        let rejected = handle.execute(increment(0)).await;
+
+        // Then
+        for receipt in [first.unwrap(), second.unwrap()] {
+            assert!(matches!(
+                receipt,
+                TransactionReceipt::Invoke(InvokeTransactionReceipt {
+                    execution_result: ExecutionResult::Succeeded,
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            rejected,
+            Err(AsyncSequencerError::TransactionExecutionError(_))
+        ));
+
+        // When
+        drop(handle);
+        let sequencer = worker.join().unwrap();
+
+        // Then
+        assert_eq!(
+            sequencer
+                .state
+                .storage_of(&TEST_CONTRACT, &get_storage_var_address("counter", &[])),
+            Some(&StarkFelt::from(2u8))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_sequencer_mine_block() {
+        // Given
+        let (handle, worker) = AsyncSequencer::spawn(counter_sequencer(), 4);
+
+        // When
+        let first = handle.execute(increment(0)).await.unwrap();
+        handle.mine_block().await.unwrap();
+        let second = handle.execute(increment(1)).await.unwrap();
+
+        // Then
+        let block_number = |receipt| match receipt {
+            TransactionReceipt::Invoke(InvokeTransactionReceipt { block_number, .. }) => {
+                block_number
+            }
+            _ => panic!("expected an invoke receipt"),
+        };
+        assert_eq!(block_number(first), ONE_BLOCK_NUMBER.0);
+        assert_eq!(block_number(second), ONE_BLOCK_NUMBER.0 + 1);
+
+        // When
+        drop(handle);
+        let sequencer = worker.join().unwrap();
+
+        // Then
+        assert_eq!(
+            sequencer.block_context().block_number.0,
+            ONE_BLOCK_NUMBER.0 + 1
+        );
+    }
+}
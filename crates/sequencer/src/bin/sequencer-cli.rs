@@ -0,0 +1,173 @@
+//! Command-line tool executing transactions offline on top of a state dump.
+//!
+//! ```text
+//! sequencer-cli --state <DUMP> --block-context <JSON> --transactions <JSON>
+//!               [--output <DUMP>] [--impersonate <ADDRESS>]...
+//! ```
+//!
+//! The transactions file holds a JSON array of transactions in the
+//! `BroadcastedTransaction` format of the Starknet JSON-RPC. The block context
+//! file holds a `SerializableBlockContext`. For each transaction, its RPC receipt
+//! and trace are printed to stdout as a JSON line.
+
+use std::{fs::File, io::BufReader, path::PathBuf, process::ExitCode};
+
+use blockifier::block_context::BlockContext;
+use eyre::{eyre, WrapErr};
+use sequencer::{
+    block_context::SerializableBlockContext,
+    execution::Execution,
+    receipt::{to_transaction_receipt, to_transaction_trace, transaction_hash, TransactionKind},
+    sequencer::Sequencer,
+    serde::DumpLoad,
+    state::State,
+    transaction::BroadcastedTransactionWrapper,
+};
+use serde::Serialize;
+use starknet::core::types::{
+    BroadcastedTransaction, FieldElement, TransactionReceipt, TransactionTrace,
+};
+use starknet_api::{
+    block::BlockHash,
+    core::{ContractAddress, PatriciaKey},
+    hash::StarkFelt,
+};
+
+const USAGE: &str = "usage: sequencer-cli --state <DUMP> --block-context <JSON> \
+--transactions <JSON> [--output <DUMP>] [--impersonate <ADDRESS>]...";
+
+#[derive(Debug, Default)]
+struct Args {
+    state: Option<PathBuf>,
+    block_context: Option<PathBuf>,
+    transactions: Option<PathBuf>,
+    output: Option<PathBuf>,
+    impersonate: Vec<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> eyre::Result<Self> {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| eyre!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--state" => parsed.state = Some(value()?.into()),
+                "--block-context" => parsed.block_context = Some(value()?.into()),
+                "--transactions" => parsed.transactions = Some(value()?.into()),
+                "--output" => parsed.output = Some(value()?.into()),
+                "--impersonate" => parsed.impersonate.push(value()?),
+                _ => return Err(eyre!("unexpected argument {}", arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Report of an executed transaction, in the RPC format. Rejected
+/// transactions only have an error.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Report {
+    Executed {
+        receipt: TransactionReceipt,
+        trace: TransactionTrace,
+    },
+    Rejected {
+        transaction_hash: StarkFelt,
+        error: String,
+    },
+}
+
+fn run(args: Args) -> eyre::Result<bool> {
+    let state_path = args.state.ok_or_else(|| eyre!("missing --state"))?;
+    let block_context_path = args
+        .block_context
+        .ok_or_else(|| eyre!("missing --block-context"))?;
+    let transactions_path = args
+        .transactions
+        .ok_or_else(|| eyre!("missing --transactions"))?;
+
+    let state = State::load_state_from_file(&state_path)
+        .wrap_err_with(|| format!("failed to load state from {}", state_path.display()))?;
+    let block_context: SerializableBlockContext =
+        serde_json::from_reader(BufReader::new(File::open(&block_context_path)?))
+            .wrap_err("failed to parse the block context")?;
+    let block_context = BlockContext::from(block_context);
+    let transactions: Vec<BroadcastedTransaction> =
+        serde_json::from_reader(BufReader::new(File::open(&transactions_path)?))
+            .wrap_err("failed to parse the transactions")?;
+
+    let chain_id = FieldElement::from_byte_slice_be(block_context.chain_id.0.as_bytes())?;
+    let mut sequencer = Sequencer::new(block_context, state, ());
+    for address in &args.impersonate {
+        let address = StarkFelt::try_from(address.as_str())?;
+        sequencer.impersonate_account(ContractAddress(PatriciaKey::try_from(address)?));
+    }
+
+    let mut all_succeeded = true;
+    for transaction in transactions {
+        let transaction = BroadcastedTransactionWrapper::new(transaction)
+            .try_into_execution_transaction(chain_id)?;
+        let transaction_hash = transaction_hash(&transaction);
+        let kind = TransactionKind::from(&transaction);
+        let block_number = sequencer.block_context().block_number;
+
+        let report = match sequencer.execute(transaction) {
+            Ok(info) => {
+                all_succeeded &= info.revert_error.is_none();
+                // Blocks aren't hashed by the sequencer.
+                Report::Executed {
+                    receipt: to_transaction_receipt(
+                        transaction_hash,
+                        kind,
+                        &info,
+                        BlockHash::default(),
+                        block_number,
+                    ),
+                    trace: to_transaction_trace(kind, &info)?,
+                }
+            }
+            Err(error) => {
+                all_succeeded = false;
+                Report::Rejected {
+                    transaction_hash: transaction_hash.0,
+                    error: error.to_string(),
+                }
+            }
+        };
+        println!("{}", serde_json::to_string(&report)?);
+    }
+
+    if let Some(output) = args.output {
+        sequencer
+            .state
+            .dump_state_to_file(&output)
+            .wrap_err_with(|| format!("failed to write state to {}", output.display()))?;
+    }
+
+    Ok(all_succeeded)
+}
+
+/// Exits with 0 if all transactions succeeded, 1 if any of them reverted or
+/// was rejected, and 2 on invalid input.
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {:?}", error);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use blockifier::block_context::{BlockContext, FeeTokenAddresses, GasPrices};
+use serde::{Deserialize, Serialize};
+use starknet_api::{
+    block::{BlockNumber, BlockTimestamp},
+    core::{ChainId, ContractAddress},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BlockContextError {
+    #[error("block number {new} is lower than the current block number {current}")]
+    BlockNumberDecrease { current: u64, new: u64 },
+    #[error("block timestamp {new} is lower than the current block timestamp {current}")]
+    BlockTimestampDecrease { current: u64, new: u64 },
+    #[error("block number overflow")]
+    BlockNumberOverflow,
+    #[error("block timestamp overflow")]
+    BlockTimestampOverflow,
+    #[error("{0} must be strictly positive")]
+    ZeroLimit(&'static str),
+}
+
+/// Builder for the block context of the sequencer.
+/// The builder is created from an existing block context, which is
+/// used as the parent of the built context: the block number and the
+/// block timestamp of the built context can't be lower than the ones of
+/// the parent.
+#[derive(Clone, Debug)]
+pub struct BlockContextBuilder {
+    parent_block_number: BlockNumber,
+    parent_block_timestamp: BlockTimestamp,
+    context: BlockContext,
+}
+
+impl From<BlockContext> for BlockContextBuilder {
+    fn from(context: BlockContext) -> Self {
+        Self {
+            parent_block_number: context.block_number,
+            parent_block_timestamp: context.block_timestamp,
+            context,
+        }
+    }
+}
+
+impl BlockContextBuilder {
+    #[must_use]
+    pub fn block_number(mut self, block_number: BlockNumber) -> Self {
+        self.context.block_number = block_number;
+        self
+    }
+
+    #[must_use]
+    pub fn block_timestamp(mut self, block_timestamp: BlockTimestamp) -> Self {
+        self.context.block_timestamp = block_timestamp;
+        self
+    }
+
+    #[must_use]
+    pub fn gas_prices(mut self, gas_prices: GasPrices) -> Self {
+        self.context.gas_prices = gas_prices;
+        self
+    }
+
+    #[must_use]
+    pub fn invoke_tx_max_n_steps(mut self, invoke_tx_max_n_steps: u32) -> Self {
+        self.context.invoke_tx_max_n_steps = invoke_tx_max_n_steps;
+        self
+    }
+
+    #[must_use]
+    pub fn validate_max_n_steps(mut self, validate_max_n_steps: u32) -> Self {
+        self.context.validate_max_n_steps = validate_max_n_steps;
+        self
+    }
+
+    #[must_use]
+    pub fn max_recursion_depth(mut self, max_recursion_depth: usize) -> Self {
+        self.context.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    /// Validates and returns the block context.
+    ///
+    /// # Errors
+    ///
+    /// If the block number or the block timestamp are lower than the parent's,
+    /// or if any of the execution limits is zero.
+    pub fn build(self) -> Result<BlockContext, BlockContextError> {
+        let context = self.context;
+
+        if context.block_number < self.parent_block_number {
+            return Err(BlockContextError::BlockNumberDecrease {
+                current: self.parent_block_number.0,
+                new: context.block_number.0,
+            });
+        }
+        if context.block_timestamp < self.parent_block_timestamp {
+            return Err(BlockContextError::BlockTimestampDecrease {
+                current: self.parent_block_timestamp.0,
+                new: context.block_timestamp.0,
+            });
+        }
+        if context.invoke_tx_max_n_steps == 0 {
+            return Err(BlockContextError::ZeroLimit("invoke_tx_max_n_steps"));
+        }
+        if context.validate_max_n_steps == 0 {
+            return Err(BlockContextError::ZeroLimit("validate_max_n_steps"));
+        }
+        if context.max_recursion_depth == 0 {
+            return Err(BlockContextError::ZeroLimit("max_recursion_depth"));
+        }
+
+        Ok(context)
+    }
+}
+
+/// Default cost of the VM resources: every resource costs one unit of gas.
+const DEFAULT_VM_RESOURCES: [&str; 10] = [
+    "n_steps",
+    "pedersen_builtin",
+    "range_check_builtin",
+    "ecdsa_builtin",
+    "bitwise_builtin",
+    "poseidon_builtin",
+    "output_builtin",
+    "ec_op_builtin",
+    "keccak_builtin",
+    "segment_arena_builtin",
+];
+
+/// Serializable representation of a block context. The VM resource costs
+/// are sorted by name, so that the serialization is deterministic.
+///
+/// Missing fields take their default value, which are the ones used by the
+/// ef-tests: chain id `KKRT`, large step limits, unit gas prices and unit VM
+/// resource costs. The addresses default to zero.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SerializableBlockContext {
+    pub chain_id: ChainId,
+    pub block_number: BlockNumber,
+    pub block_timestamp: BlockTimestamp,
+    pub sequencer_address: ContractAddress,
+    pub eth_fee_token_address: ContractAddress,
+    pub strk_fee_token_address: ContractAddress,
+    pub vm_resource_fee_cost: BTreeMap<String, f64>,
+    pub eth_l1_gas_price: u128,
+    pub strk_l1_gas_price: u128,
+    pub invoke_tx_max_n_steps: u32,
+    pub validate_max_n_steps: u32,
+    pub max_recursion_depth: usize,
+}
+
+impl Default for SerializableBlockContext {
+    fn default() -> Self {
+        Self {
+            chain_id: ChainId("KKRT".into()),
+            block_number: BlockNumber(0),
+            block_timestamp: BlockTimestamp(0),
+            sequencer_address: ContractAddress::default(),
+            eth_fee_token_address: ContractAddress::default(),
+            strk_fee_token_address: ContractAddress::default(),
+            vm_resource_fee_cost: DEFAULT_VM_RESOURCES
+                .into_iter()
+                .map(|resource| (resource.to_string(), 1_f64))
+                .collect(),
+            eth_l1_gas_price: 1,
+            strk_l1_gas_price: 1,
+            invoke_tx_max_n_steps: 50_000_000,
+            validate_max_n_steps: 50_000_000,
+            max_recursion_depth: 8192,
+        }
+    }
+}
+
+impl From<&BlockContext> for SerializableBlockContext {
+    fn from(context: &BlockContext) -> Self {
+        Self {
+            chain_id: context.chain_id.clone(),
+            block_number: context.block_number,
+            block_timestamp: context.block_timestamp,
+            sequencer_address: context.sequencer_address,
+            eth_fee_token_address: context.fee_token_addresses.eth_fee_token_address,
+            strk_fee_token_address: context.fee_token_addresses.strk_fee_token_address,
+            vm_resource_fee_cost: context
+                .vm_resource_fee_cost
+                .iter()
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            eth_l1_gas_price: context.gas_prices.eth_l1_gas_price,
+            strk_l1_gas_price: context.gas_prices.strk_l1_gas_price,
+            invoke_tx_max_n_steps: context.invoke_tx_max_n_steps,
+            validate_max_n_steps: context.validate_max_n_steps,
+            max_recursion_depth: context.max_recursion_depth,
+        }
+    }
+}
+
+impl From<SerializableBlockContext> for BlockContext {
+    fn from(context: SerializableBlockContext) -> Self {
+        Self {
+            chain_id: context.chain_id,
+            block_number: context.block_number,
+            block_timestamp: context.block_timestamp,
+            sequencer_address: context.sequencer_address,
+            fee_token_addresses: FeeTokenAddresses {
+                strk_fee_token_address: context.strk_fee_token_address,
+                eth_fee_token_address: context.eth_fee_token_address,
+            },
+            vm_resource_fee_cost: Arc::new(context.vm_resource_fee_cost.into_iter().collect()),
+            gas_prices: GasPrices {
+                eth_l1_gas_price: context.eth_l1_gas_price,
+                strk_l1_gas_price: context.strk_l1_gas_price,
+            },
+            invoke_tx_max_n_steps: context.invoke_tx_max_n_steps,
+            validate_max_n_steps: context.validate_max_n_steps,
+            max_recursion_depth: context.max_recursion_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::test_constants::block_context;
+
+    use super::*;
+
+    #[test]
+    fn test_build_block_context() {
+        // Given
+        let builder = BlockContextBuilder::from(block_context());
+
+        // When
+        let context = builder
+            .block_number(BlockNumber(10))
+            .block_timestamp(BlockTimestamp(100))
+            .gas_prices(GasPrices {
+                eth_l1_gas_price: 2,
+                strk_l1_gas_price: 3,
+            })
+            .build()
+            .unwrap();
+
+        // Then
+        assert_eq!(context.block_number, BlockNumber(10));
+        assert_eq!(context.block_timestamp, BlockTimestamp(100));
+        assert_eq!(context.gas_prices.eth_l1_gas_price, 2);
+        assert_eq!(context.gas_prices.strk_l1_gas_price, 3);
+    }
+
+    #[test]
+    fn test_build_block_context_decreasing_block_number() {
+        // Given
+        let builder = BlockContextBuilder::from(block_context());
+
+        // When
+        let result = builder.block_number(BlockNumber(0)).build();
+
+        // Then
+        assert_eq!(
+            result.unwrap_err(),
+            BlockContextError::BlockNumberDecrease { current: 1, new: 0 }
+        );
+    }
+
+    #[test]
+    fn test_build_block_context_decreasing_block_timestamp() {
+        // Given
+        let builder = BlockContextBuilder::from(block_context());
+
+        // When
+        let result = builder.block_timestamp(BlockTimestamp(0)).build();
+
+        // Then
+        assert_eq!(
+            result.unwrap_err(),
+            BlockContextError::BlockTimestampDecrease { current: 1, new: 0 }
+        );
+    }
+
+    #[test]
+    fn test_build_block_context_zero_limit() {
+        // Given
+        let builder = BlockContextBuilder::from(block_context());
+
+        // When
+        let result = builder.invoke_tx_max_n_steps(0).build();
+
+        // Then
+        assert_eq!(
+            result.unwrap_err(),
+            BlockContextError::ZeroLimit("invoke_tx_max_n_steps")
+        );
+    }
+
+    #[test]
+    fn test_serializable_block_context() {
+        // Given
+        let mut context = block_context();
+        context.vm_resource_fee_cost =
+            Arc::new([(String::from("n_steps"), 1_f64)].into_iter().collect());
+
+        // When
+        let serializable = SerializableBlockContext::from(&context);
+        let json = serde_json::to_string(&serializable).unwrap();
+        let deserialized: SerializableBlockContext = serde_json::from_str(&json).unwrap();
+        let round_trip = BlockContext::from(deserialized.clone());
+
+        // Then
+        assert_eq!(serializable, deserialized);
+        assert_eq!(round_trip.chain_id, context.chain_id);
+        assert_eq!(round_trip.block_number, context.block_number);
+        assert_eq!(
+            round_trip.vm_resource_fee_cost,
+            context.vm_resource_fee_cost
+        );
+        assert_eq!(
+            round_trip.fee_token_addresses.eth_fee_token_address,
+            context.fee_token_addresses.eth_fee_token_address
+        );
+    }
+}
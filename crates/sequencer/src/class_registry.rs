@@ -0,0 +1,296 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use blockifier::execution::{
+    contract_class::{ContractClass, ContractClassV0, ContractClassV1},
+    execution_utils::felt_to_stark_felt,
+};
+use cairo_lang_starknet::{
+    casm_contract_class::CasmContractClass, contract_class::ContractClass as SierraContractClass,
+};
+use cairo_vm::types::errors::program_errors::ProgramError;
+use lru::LruCache;
+use rustc_hash::FxHashMap;
+use serde::{de::IgnoredAny, Deserialize};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash},
+    hash::StarkFelt,
+    StarknetApiError,
+};
+use thiserror::Error;
+
+use crate::sierra::{compile_sierra_class_uncached, SierraCompilationError};
+
+#[derive(Error, Debug)]
+pub enum ClassRegistryError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    ProgramError(#[from] ProgramError),
+    #[error(transparent)]
+    SierraCompilationError(#[from] SierraCompilationError),
+    #[error(transparent)]
+    StarknetApiError(#[from] StarknetApiError),
+    #[error("unknown class format in {0}")]
+    UnknownFormat(PathBuf),
+}
+
+/// Loaded class, along with its compiled class hash when it's a Cairo 1
+/// class.
+type LoadedClass = (ContractClass, Option<CompiledClassHash>);
+
+/// Registry of contract classes stored on disk, indexed by class hash.
+/// Classes are only read, and compiled in the case of Sierra classes, when
+/// first accessed. At most `capacity` loaded classes are kept in memory, the
+/// least recently used ones being evicted first.
+///
+/// The format of each file is detected from its content: Cairo 0 compiled
+/// classes, CASM classes and Sierra classes are supported.
+pub struct ClassRegistry {
+    index: FxHashMap<ClassHash, PathBuf>,
+    cache: LruCache<ClassHash, LoadedClass>,
+}
+
+impl fmt::Debug for ClassRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClassRegistry")
+            .field("indexed", &self.index.len())
+            .field("loaded", &self.cache.len())
+            .finish()
+    }
+}
+
+impl ClassRegistry {
+    /// Creates an empty registry keeping at most `capacity` loaded classes
+    /// in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            index: FxHashMap::default(),
+            cache: LruCache::new(capacity),
+        }
+    }
+
+    /// Registers the class file for the class hash.
+    pub fn register(&mut self, class_hash: ClassHash, path: PathBuf) {
+        self.cache.pop(&class_hash);
+        self.index.insert(class_hash, path);
+    }
+
+    /// Registers all the class files of the directory, which must be named
+    /// after their class hash (e.g. `0x1234.json`). Other files are ignored.
+    /// Returns the number of registered classes.
+    ///
+    /// # Errors
+    ///
+    /// If the directory can't be read.
+    pub fn register_dir(&mut self, dir: &Path) -> Result<usize, ClassRegistryError> {
+        let mut registered = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "json")
+            {
+                continue;
+            }
+            let Some(class_hash) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| StarkFelt::try_from(stem).ok())
+            else {
+                continue;
+            };
+            self.register(ClassHash(class_hash), path);
+            registered += 1;
+        }
+        Ok(registered)
+    }
+
+    /// Returns true if a class file is registered for the class hash.
+    pub fn contains(&self, class_hash: &ClassHash) -> bool {
+        self.index.contains_key(class_hash)
+    }
+
+    /// Returns the number of registered classes.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns the number of classes currently loaded in memory.
+    pub fn loaded(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Returns the class, loading it from disk if needed. Returns `None` if
+    /// no class file is registered for the class hash.
+    ///
+    /// # Errors
+    ///
+    /// If the class fails to load or to compile.
+    pub fn get(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Option<ContractClass>, ClassRegistryError> {
+        Ok(self.load(class_hash)?.map(|(class, _)| class))
+    }
+
+    /// Returns the compiled class hash of the class, if the class was
+    /// registered as a Sierra or a CASM class. Cairo 0 classes have no
+    /// compiled class hash.
+    ///
+    /// # Errors
+    ///
+    /// If the class fails to load or to compile.
+    pub fn compiled_class_hash(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> Result<Option<CompiledClassHash>, ClassRegistryError> {
+        Ok(self
+            .load(class_hash)?
+            .and_then(|(_, compiled_class_hash)| compiled_class_hash))
+    }
+
+    fn load(&mut self, class_hash: &ClassHash) -> Result<Option<LoadedClass>, ClassRegistryError> {
+        if let Some(loaded) = self.cache.get(class_hash) {
+            return Ok(Some(loaded.clone()));
+        }
+        let Some(path) = self.index.get(class_hash) else {
+            return Ok(None);
+        };
+
+        let loaded = load_class_file(path)?;
+        self.cache.put(*class_hash, loaded.clone());
+        Ok(Some(loaded))
+    }
+}
+
+/// Top-level keys identifying the format of a class file. The values are
+/// skipped without being parsed.
+#[derive(Deserialize)]
+struct ClassFormat {
+    sierra_program: Option<IgnoredAny>,
+    bytecode: Option<IgnoredAny>,
+    program: Option<IgnoredAny>,
+}
+
+/// Loads the class file, detecting its format from its top-level keys, then
+/// parsing it once into the detected format. Sierra classes are compiled,
+/// bypassing the process wide cache of
+/// [`crate::sierra::compile_sierra_class_with_hash`]: the registry keeps at
+/// most `capacity` classes in memory. The compiled class hash of CASM classes
+/// is computed from the class.
+pub(crate) fn load_class_file(path: &Path) -> Result<LoadedClass, ClassRegistryError> {
+    let raw_class = fs::read_to_string(path)?;
+    let format: ClassFormat = serde_json::from_str(&raw_class)?;
+
+    if format.sierra_program.is_some() {
+        let sierra_class: SierraContractClass = serde_json::from_str(&raw_class)?;
+        let (class, compiled_class_hash) = compile_sierra_class_uncached(sierra_class)?;
+        return Ok((class, Some(compiled_class_hash)));
+    }
+    if format.bytecode.is_some() {
+        let casm_class: CasmContractClass = serde_json::from_str(&raw_class)?;
+        let compiled_class_hash =
+            CompiledClassHash(felt_to_stark_felt(&casm_class.compiled_class_hash()));
+        let class = ContractClassV1::try_from(casm_class)?;
+        return Ok((ContractClass::V1(class), Some(compiled_class_hash)));
+    }
+    if format.program.is_some() {
+        let class: ContractClassV0 = serde_json::from_str(&raw_class)?;
+        return Ok((ContractClass::V0(class), None));
+    }
+
+    Err(ClassRegistryError::UnknownFormat(path.to_path_buf()))
+}
+
+/// Class registry shared between the clones of a state. Classes are
+/// immutable once indexed by their hash, so sharing the registry never
+/// changes what a state reads.
+#[derive(Clone, Debug)]
+pub struct SharedClassRegistry(Arc<Mutex<ClassRegistry>>);
+
+impl SharedClassRegistry {
+    pub fn new(registry: ClassRegistry) -> Self {
+        Self(Arc::new(Mutex::new(registry)))
+    }
+
+    /// Locks the registry. A poisoned lock is recovered, since a panic while
+    /// loading a class leaves the registry consistent.
+    pub fn lock(&self) -> MutexGuard<'_, ClassRegistry> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constants::test_constants::{ONE_CLASS_HASH, TWO_CLASS_HASH},
+        serde::canonical::class_path,
+    };
+
+    use super::*;
+
+    const COUNTER_CLASS: &str = include_str!("./test_data/cairo_0/compiled_classes/counter.json");
+    const ACCOUNT_CLASS: &str = include_str!("./test_data/cairo_1/compiled_classes/account.json");
+
+    fn registry_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(class_path(dir.path(), &ONE_CLASS_HASH), COUNTER_CLASS).unwrap();
+        fs::write(class_path(dir.path(), &TWO_CLASS_HASH), ACCOUNT_CLASS).unwrap();
+        fs::write(dir.path().join("README.md"), "not a class").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_register_dir() {
+        // Given
+        let dir = registry_dir();
+        let mut registry = ClassRegistry::new(2);
+
+        // When
+        let registered = registry.register_dir(dir.path()).unwrap();
+
+        // Then
+        assert_eq!(registered, 2);
+        assert!(registry.contains(&ONE_CLASS_HASH));
+        assert!(registry.contains(&TWO_CLASS_HASH));
+        assert_eq!(registry.loaded(), 0);
+    }
+
+    #[test]
+    fn test_lazy_load_and_eviction() {
+        // Given
+        let dir = registry_dir();
+        let mut registry = ClassRegistry::new(1);
+        registry.register_dir(dir.path()).unwrap();
+
+        // When
+        let counter = registry.get(&ONE_CLASS_HASH).unwrap().unwrap();
+        let account = registry.get(&TWO_CLASS_HASH).unwrap().unwrap();
+
+        // Then
+        assert!(matches!(counter, ContractClass::V0(_)));
+        assert!(matches!(account, ContractClass::V1(_)));
+        assert_eq!(registry.loaded(), 1);
+        let casm_class: CasmContractClass = serde_json::from_str(ACCOUNT_CLASS).unwrap();
+        assert_eq!(
+            registry.compiled_class_hash(&TWO_CLASS_HASH).unwrap(),
+            Some(CompiledClassHash(felt_to_stark_felt(
+                &casm_class.compiled_class_hash()
+            )))
+        );
+        assert_eq!(registry.compiled_class_hash(&ONE_CLASS_HASH).unwrap(), None);
+        assert!(registry.get(&ClassHash::default()).unwrap().is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use blockifier::state::{
-    cached_state::CachedState,
+    cached_state::{CachedState, CommitmentStateDiff},
     state_api::{State as BlockifierState, StateReader as BlockifierStateReader, StateResult},
 };
 
@@ -12,22 +12,28 @@ where
 {
     fn commit(cached_state: &mut CachedState<&mut S>) -> StateResult<()> {
         let diff = cached_state.to_state_diff();
-        for (address, class_hash) in diff.address_to_class_hash {
-            cached_state.state.set_class_hash_at(address, class_hash)?;
-        }
-        for (address, _) in diff.address_to_nonce {
-            cached_state.state.increment_nonce(address)?;
-        }
-        for (address, storage_updates) in diff.storage_updates {
-            for (k, v) in storage_updates {
-                cached_state.state.set_storage_at(address, k, v);
-            }
-        }
-        for (class_hash, compiled_class_hash) in diff.class_hash_to_compiled_class_hash {
-            cached_state
-                .state
-                .set_compiled_class_hash(class_hash, compiled_class_hash)?;
+        apply_state_diff(&mut cached_state.state, diff)
+    }
+}
+
+/// Applies the state diff of a cached state to the state it wraps.
+pub(crate) fn apply_state_diff<S>(state: &mut &mut S, diff: CommitmentStateDiff) -> StateResult<()>
+where
+    for<'any> &'any mut S: BlockifierState,
+{
+    for (address, class_hash) in diff.address_to_class_hash {
+        state.set_class_hash_at(address, class_hash)?;
+    }
+    for (address, _) in diff.address_to_nonce {
+        state.increment_nonce(address)?;
+    }
+    for (address, storage_updates) in diff.storage_updates {
+        for (k, v) in storage_updates {
+            state.set_storage_at(address, k, v);
         }
-        Ok(())
     }
+    for (class_hash, compiled_class_hash) in diff.class_hash_to_compiled_class_hash {
+        state.set_compiled_class_hash(class_hash, compiled_class_hash)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use blockifier::block_context::{BlockContext, FeeTokenAddresses, GasPrices};
+use serde::{Deserialize, Serialize};
+use starknet_api::{
+    block::{BlockNumber, BlockTimestamp},
+    core::{ChainId, ContractAddress},
+};
+use thiserror::Error;
+
+use crate::block_context::{BlockContextBuilder, BlockContextError, SerializableBlockContext};
+
+#[derive(Error, Debug)]
+pub enum BlockContextConfigError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    TomlError(#[from] toml::de::Error),
+    #[error(transparent)]
+    BlockContextError(#[from] BlockContextError),
+    #[error("unknown config format for {0}, expected a .toml or .json file")]
+    UnknownFormat(PathBuf),
+}
+
+/// Chain and block configuration of the sequencer, from which the block
+/// context is built. The configuration can be loaded from a TOML or a JSON
+/// file, in which any missing field takes its default value (see
+/// [`SerializableBlockContext`]), or assembled through its setters.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlockContextConfig(SerializableBlockContext);
+
+impl BlockContextConfig {
+    /// Loads the configuration from a TOML or a JSON file, depending on its
+    /// extension.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read or parsed, or if its extension is unknown.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, BlockContextConfigError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension {
+            Some("toml") => Self::from_toml(&fs::read_to_string(path)?),
+            Some("json") => Ok(serde_json::from_reader(std::io::BufReader::new(
+                fs::File::open(path)?,
+            ))?),
+            _ => Err(BlockContextConfigError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Parses the configuration from a TOML string.
+    ///
+    /// # Errors
+    ///
+    /// If the string isn't a valid configuration.
+    pub fn from_toml(raw: &str) -> Result<Self, BlockContextConfigError> {
+        Ok(toml::from_str(raw)?)
+    }
+
+    #[must_use]
+    pub fn chain_id(mut self, chain_id: ChainId) -> Self {
+        self.0.chain_id = chain_id;
+        self
+    }
+
+    #[must_use]
+    pub fn block_number(mut self, block_number: BlockNumber) -> Self {
+        self.0.block_number = block_number;
+        self
+    }
+
+    #[must_use]
+    pub fn block_timestamp(mut self, block_timestamp: BlockTimestamp) -> Self {
+        self.0.block_timestamp = block_timestamp;
+        self
+    }
+
+    #[must_use]
+    pub fn sequencer_address(mut self, sequencer_address: ContractAddress) -> Self {
+        self.0.sequencer_address = sequencer_address;
+        self
+    }
+
+    #[must_use]
+    pub fn fee_token_addresses(mut self, fee_token_addresses: FeeTokenAddresses) -> Self {
+        self.0.eth_fee_token_address = fee_token_addresses.eth_fee_token_address;
+        self.0.strk_fee_token_address = fee_token_addresses.strk_fee_token_address;
+        self
+    }
+
+    #[must_use]
+    pub fn vm_resource_fee_cost(mut self, vm_resource_fee_cost: BTreeMap<String, f64>) -> Self {
+        self.0.vm_resource_fee_cost = vm_resource_fee_cost;
+        self
+    }
+
+    #[must_use]
+    pub fn gas_prices(mut self, gas_prices: GasPrices) -> Self {
+        self.0.eth_l1_gas_price = gas_prices.eth_l1_gas_price;
+        self.0.strk_l1_gas_price = gas_prices.strk_l1_gas_price;
+        self
+    }
+
+    #[must_use]
+    pub fn invoke_tx_max_n_steps(mut self, invoke_tx_max_n_steps: u32) -> Self {
+        self.0.invoke_tx_max_n_steps = invoke_tx_max_n_steps;
+        self
+    }
+
+    #[must_use]
+    pub fn validate_max_n_steps(mut self, validate_max_n_steps: u32) -> Self {
+        self.0.validate_max_n_steps = validate_max_n_steps;
+        self
+    }
+
+    #[must_use]
+    pub fn max_recursion_depth(mut self, max_recursion_depth: usize) -> Self {
+        self.0.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    /// Validates the configuration and returns the block context.
+    ///
+    /// # Errors
+    ///
+    /// If any of the execution limits is zero.
+    pub fn build(self) -> Result<BlockContext, BlockContextError> {
+        BlockContextBuilder::from(BlockContext::from(self.0)).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::test_constants::{ETH_FEE_TOKEN_ADDRESS, SEQUENCER_ADDRESS};
+
+    use super::*;
+
+    #[test]
+    fn test_config_from_toml() {
+        // Given
+        let raw = r#"
+            invoke_tx_max_n_steps = 1000000
+            eth_l1_gas_price = 100
+            sequencer_address = "0x4d2"
+
+            [vm_resource_fee_cost]
+            n_steps = 0.5
+        "#;
+
+        // When
+        let config = BlockContextConfig::from_toml(raw).unwrap();
+        let context = config.build().unwrap();
+
+        // Then
+        assert_eq!(context.invoke_tx_max_n_steps, 1_000_000);
+        assert_eq!(context.validate_max_n_steps, 50_000_000);
+        assert_eq!(context.gas_prices.eth_l1_gas_price, 100);
+        assert_eq!(context.gas_prices.strk_l1_gas_price, 1);
+        assert_eq!(context.sequencer_address, *SEQUENCER_ADDRESS);
+        assert_eq!(context.chain_id, ChainId("KKRT".into()));
+        assert_eq!(context.vm_resource_fee_cost.len(), 1);
+        assert_eq!(context.vm_resource_fee_cost.get("n_steps"), Some(&0.5));
+    }
+
+    #[test]
+    fn test_config_from_json_file() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let config = BlockContextConfig::default()
+            .fee_token_addresses(FeeTokenAddresses {
+                strk_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
+                eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
+            })
+            .max_recursion_depth(100);
+        fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        // When
+        let loaded = BlockContextConfig::from_file(&path).unwrap();
+
+        // Then
+        assert_eq!(loaded, config);
+        assert_eq!(loaded.build().unwrap().max_recursion_depth, 100);
+    }
+
+    #[test]
+    fn test_config_invalid() {
+        // Given
+        let unknown_field = "max_steps = 10";
+        let zero_limit = BlockContextConfig::default().validate_max_n_steps(0);
+
+        // When
+        let unknown_field = BlockContextConfig::from_toml(unknown_field);
+        let zero_limit = zero_limit.build();
+
+        // Then
+        assert!(matches!(
+            unknown_field,
+            Err(BlockContextConfigError::TomlError(_))
+        ));
+        assert_eq!(
+            zero_limit.unwrap_err(),
+            BlockContextError::ZeroLimit("validate_max_n_steps")
+        );
+    }
+}
@@ -1,13 +1,28 @@
 #[cfg(test)]
 pub mod test_constants {
+    use blockifier::{
+        block_context::{BlockContext, FeeTokenAddresses},
+        execution::contract_class::{ContractClass, ContractClassV0, ContractClassV1},
+        transaction::{
+            account_transaction::AccountTransaction, transaction_execution::Transaction,
+            transactions::InvokeTransaction as BlockifierInvokeTransaction,
+        },
+    };
     use starknet::core::types::FieldElement;
+    use starknet::macros::selector;
     use starknet_api::{
         block::{BlockNumber, BlockTimestamp},
         core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
         hash::StarkFelt,
         state::StorageKey,
+        transaction::{
+            Calldata, Fee, InvokeTransaction, InvokeTransactionV1, TransactionHash,
+            TransactionSignature,
+        },
     };
 
+    use crate::{config::BlockContextConfig, sequencer::Sequencer, state::State};
+
     lazy_static::lazy_static! {
         pub static ref TEST_CONTRACT: ContractAddress = ContractAddress(*ONE_PATRICIA);
         pub static ref TEST_ACCOUNT: ContractAddress = ContractAddress(*TWO_PATRICIA);
@@ -29,4 +44,77 @@ pub mod test_constants {
         pub static ref ONE_BLOCK_NUMBER: BlockNumber = BlockNumber(1);
         pub static ref ONE_BLOCK_TIMESTAMP: BlockTimestamp = BlockTimestamp(1);
     }
+
+    /// Returns the block context of the tests: the default configuration, at
+    /// the first block and with the test addresses.
+    pub fn block_context() -> BlockContext {
+        BlockContextConfig::default()
+            .block_number(*ONE_BLOCK_NUMBER)
+            .block_timestamp(*ONE_BLOCK_TIMESTAMP)
+            .sequencer_address(*SEQUENCER_ADDRESS)
+            .fee_token_addresses(FeeTokenAddresses {
+                strk_fee_token_address: *STRK_FEE_TOKEN_ADDRESS,
+                eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
+            })
+            .build()
+            .unwrap()
+    }
+
+    /// Reads a compiled class from the file, either a Cairo 0 class or a
+    /// Cairo 1 CASM class.
+    pub fn read_contract_class(path: &str) -> ContractClass {
+        let raw_class = std::fs::read_to_string(path).unwrap();
+        match serde_json::from_str::<ContractClassV0>(&raw_class) {
+            Ok(class) => ContractClass::V0(class),
+            Err(_) => ContractClass::V1(ContractClassV1::try_from_json_string(&raw_class).unwrap()),
+        }
+    }
+
+    /// Returns a sequencer at the block of [`block_context`], with the Cairo 0
+    /// counter deployed at the test contract and the Cairo 0 account, funded,
+    /// at the test account.
+    pub fn counter_sequencer() -> Sequencer<State, ()> {
+        let mut sequencer = Sequencer::new(block_context(), State::default(), ());
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        sequencer
+            .deploy_class_at(
+                *TEST_ACCOUNT,
+                *TWO_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/account.json"),
+            )
+            .unwrap();
+        sequencer
+            .set_fee_token_balance(*TEST_ACCOUNT, [u128::MAX, 0])
+            .unwrap();
+        sequencer
+    }
+
+    /// Returns an invoke of `inc` on the test contract, sent by the test
+    /// account with the nonce, which is also used as transaction hash.
+    pub fn increment(nonce: u8) -> Transaction {
+        Transaction::AccountTransaction(AccountTransaction::Invoke(BlockifierInvokeTransaction {
+            tx: InvokeTransaction::V1(InvokeTransactionV1 {
+                sender_address: *TEST_ACCOUNT,
+                calldata: Calldata(
+                    vec![
+                        *TEST_CONTRACT.0.key(), // destination
+                        selector!("inc").into(),
+                        *ZERO_FELT, // no data
+                    ]
+                    .into(),
+                ),
+                max_fee: Fee(1_000_000),
+                signature: TransactionSignature(vec![]),
+                nonce: Nonce(StarkFelt::from(nonce)),
+            }),
+            only_query: false,
+            tx_hash: TransactionHash(StarkFelt::from(nonce)),
+        }))
+    }
 }
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use blockifier::state::cached_state::ContractStorageKey;
+use serde::{Deserialize, Serialize};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
+    hash::StarkFelt,
+};
+
+use crate::state::{backend::MapBackend, GenericState};
+
+/// Value of an entry before and after a change.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change<V> {
+    pub before: V,
+    pub after: V,
+}
+
+/// Added, removed and changed entries of a map.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapDiff<K, V> {
+    pub added: Vec<(K, V)>,
+    pub removed: Vec<(K, V)>,
+    pub changed: Vec<(K, Change<V>)>,
+}
+
+impl<K, V> Default for MapDiff<K, V> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> MapDiff<K, V> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Added, removed and changed classes. Classes are only referenced by
+/// their class hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassesDiff {
+    pub added: Vec<ClassHash>,
+    pub removed: Vec<ClassHash>,
+    pub changed: Vec<ClassHash>,
+}
+
+impl ClassesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Structured diff between two states. All entries are sorted by key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiff {
+    pub contracts: MapDiff<ContractAddress, ClassHash>,
+    pub storage: MapDiff<ContractStorageKey, StarkFelt>,
+    pub nonces: MapDiff<ContractAddress, Nonce>,
+    pub classes: ClassesDiff,
+    pub compiled_class_hashes: MapDiff<ClassHash, CompiledClassHash>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+            && self.storage.is_empty()
+            && self.nonces.is_empty()
+            && self.classes.is_empty()
+            && self.compiled_class_hashes.is_empty()
+    }
+
+    /// Returns the JSON representation of the diff.
+    ///
+    /// # Errors
+    ///
+    /// If the serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl<B: MapBackend> GenericState<B> {
+    /// Computes the diff from the current state to the other state. Entries
+    /// are compared by reference and only the changed ones are cloned.
+    pub fn diff(&self, other: &Self) -> StateDiff {
+        let classes = diff_maps(self.classes().collect(), other.classes().collect());
+
+        StateDiff {
+            contracts: diff_maps(self.contracts().collect(), other.contracts().collect()).cloned(),
+            storage: diff_maps(self.storage().collect(), other.storage().collect()).cloned(),
+            nonces: diff_maps(self.nonces().collect(), other.nonces().collect()).cloned(),
+            classes: ClassesDiff {
+                added: classes.added.into_iter().map(|(k, _)| *k).collect(),
+                removed: classes.removed.into_iter().map(|(k, _)| *k).collect(),
+                changed: classes.changed.into_iter().map(|(k, _)| *k).collect(),
+            },
+            compiled_class_hashes: diff_maps(
+                self.compiled_class_hashes().collect(),
+                other.compiled_class_hashes().collect(),
+            )
+            .cloned(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> MapDiff<&K, &V> {
+    fn cloned(self) -> MapDiff<K, V> {
+        let entry = |(k, v): (&K, &V)| (k.clone(), v.clone());
+        MapDiff {
+            added: self.added.into_iter().map(entry).collect(),
+            removed: self.removed.into_iter().map(entry).collect(),
+            changed: self
+                .changed
+                .into_iter()
+                .map(|(k, change)| {
+                    (
+                        k.clone(),
+                        Change {
+                            before: change.before.clone(),
+                            after: change.after.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+fn diff_maps<'a, K, V>(
+    before: BTreeMap<&'a K, &'a V>,
+    after: BTreeMap<&'a K, &'a V>,
+) -> MapDiff<&'a K, &'a V>
+where
+    K: Ord,
+    V: PartialEq,
+{
+    let mut diff = MapDiff::default();
+    for (&k, &v) in &before {
+        match after.get(k) {
+            None => diff.removed.push((k, v)),
+            Some(&after) if after != v => diff.changed.push((k, Change { before: v, after })),
+            Some(_) => {}
+        }
+    }
+    for (k, v) in after {
+        if !before.contains_key(k) {
+            diff.added.push((k, v));
+        }
+    }
+    diff
+}
+
+fn write_section<K, V>(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    diff: &MapDiff<K, V>,
+    fmt_key: impl Fn(&K) -> String,
+    fmt_value: impl Fn(&V) -> String,
+) -> fmt::Result {
+    if diff.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{}:", name)?;
+    for (k, v) in &diff.added {
+        writeln!(f, "  + {}: {}", fmt_key(k), fmt_value(v))?;
+    }
+    for (k, v) in &diff.removed {
+        writeln!(f, "  - {}: {}", fmt_key(k), fmt_value(v))?;
+    }
+    for (k, change) in &diff.changed {
+        writeln!(
+            f,
+            "  ~ {}: {} -> {}",
+            fmt_key(k),
+            fmt_value(&change.before),
+            fmt_value(&change.after)
+        )?;
+    }
+    Ok(())
+}
+
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no difference");
+        }
+
+        let address = |address: &ContractAddress| address.0.key().to_string();
+        write_section(f, "contracts", &self.contracts, address, |class_hash| {
+            class_hash.0.to_string()
+        })?;
+        write_section(
+            f,
+            "storage",
+            &self.storage,
+            |(address, key)| format!("{}[{}]", address.0.key(), key.0.key()),
+            ToString::to_string,
+        )?;
+        write_section(f, "nonces", &self.nonces, address, |nonce| {
+            nonce.0.to_string()
+        })?;
+
+        if !self.classes.is_empty() {
+            writeln!(f, "classes:")?;
+            for class_hash in &self.classes.added {
+                writeln!(f, "  + {}", class_hash.0)?;
+            }
+            for class_hash in &self.classes.removed {
+                writeln!(f, "  - {}", class_hash.0)?;
+            }
+            for class_hash in &self.classes.changed {
+                writeln!(f, "  ~ {}", class_hash.0)?;
+            }
+        }
+
+        write_section(
+            f,
+            "compiled class hashes",
+            &self.compiled_class_hashes,
+            |class_hash| class_hash.0.to_string(),
+            |compiled_class_hash| compiled_class_hash.0.to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{
+        execution::contract_class::{ContractClass, ContractClassV0},
+        state::state_api::State as _,
+    };
+    use starknet_api::state::StorageKey;
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT,
+        TEST_CONTRACT, TWO_CLASS_HASH, TWO_FELT, TWO_PATRICIA,
+    };
+    use crate::state::State;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_identical_states() {
+        // Given
+        let mut state = State::default();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+
+        // When
+        let diff = state.diff(&state.clone());
+
+        // Then
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no difference\n");
+    }
+
+    #[test]
+    fn test_diff() {
+        // Given
+        let mut before = State::default();
+        (&mut before)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut before).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        (&mut before).set_storage_at(*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *ONE_FELT);
+        before.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+
+        let mut after = before.clone();
+        (&mut after).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        (&mut after)
+            .set_class_hash_at(*TEST_ACCOUNT, *TWO_CLASS_HASH)
+            .unwrap();
+        (&mut after)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut after)
+            .set_compiled_class_hash(*TWO_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)
+            .unwrap();
+        after.set_nonce(*TEST_ACCOUNT, Nonce(*TWO_FELT));
+
+        // When
+        let diff = before.diff(&after);
+
+        // Then
+        let expected = StateDiff {
+            contracts: MapDiff {
+                added: vec![(*TEST_ACCOUNT, *TWO_CLASS_HASH)],
+                ..Default::default()
+            },
+            storage: MapDiff {
+                changed: vec![(
+                    (*TEST_CONTRACT, StorageKey(*ONE_PATRICIA)),
+                    Change {
+                        before: *ONE_FELT,
+                        after: *TWO_FELT,
+                    },
+                )],
+                ..Default::default()
+            },
+            nonces: MapDiff {
+                changed: vec![(
+                    *TEST_ACCOUNT,
+                    Change {
+                        before: Nonce(*ONE_FELT),
+                        after: Nonce(*TWO_FELT),
+                    },
+                )],
+                ..Default::default()
+            },
+            classes: ClassesDiff {
+                added: vec![*TWO_CLASS_HASH],
+                ..Default::default()
+            },
+            compiled_class_hashes: MapDiff {
+                added: vec![(*TWO_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)],
+                ..Default::default()
+            },
+        };
+        assert_eq!(diff, expected);
+
+        let reverse = after.diff(&before);
+        assert_eq!(
+            reverse.contracts.removed,
+            vec![(*TEST_ACCOUNT, *TWO_CLASS_HASH)]
+        );
+        assert_eq!(reverse.classes.removed, vec![*TWO_CLASS_HASH]);
+    }
+
+    #[test]
+    fn test_diff_json() {
+        // Given
+        let before = State::default();
+        let mut after = State::default();
+        (&mut after).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+
+        // When
+        let diff = before.diff(&after);
+        let json = diff.to_json().unwrap();
+
+        // Then
+        let deserialized: StateDiff = serde_json::from_str(&json).unwrap();
+        assert_eq!(diff, deserialized);
+    }
+}
@@ -0,0 +1,494 @@
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::state::cached_state::{CommitmentStateDiff, ContractStorageKey};
+use blockifier::state::errors::StateError;
+use blockifier::state::state_api::{
+    State as BlockifierState, StateReader as BlockifierStateReader, StateResult,
+};
+use rustc_hash::FxHashMap;
+use starknet_api::{
+    block::BlockNumber,
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+
+use crate::{commit::Committer, state::State};
+
+/// Read access to the state of a remote chain. All reads are made at the
+/// provided block number. Entries which are absent from the remote state are
+/// read as zero, like in the local state, except for classes and compiled
+/// class hashes which are returned as `None`.
+pub trait RemoteStateReader {
+    /// # Errors
+    ///
+    /// If the read from the remote fails.
+    fn get_storage_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt>;
+
+    /// # Errors
+    ///
+    /// If the read from the remote fails.
+    fn get_nonce_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<Nonce>;
+
+    /// # Errors
+    ///
+    /// If the read from the remote fails.
+    fn get_class_hash_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<ClassHash>;
+
+    /// # Errors
+    ///
+    /// If the read from the remote fails.
+    fn get_contract_class(
+        &self,
+        block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<ContractClass>>;
+
+    /// # Errors
+    ///
+    /// If the read from the remote fails.
+    fn get_compiled_class_hash(
+        &self,
+        block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<CompiledClassHash>>;
+}
+
+/// Local state used as a remote, ignoring the block number. A dump loaded
+/// through [`crate::serde::DumpLoad`] can be used to fork a chain offline.
+impl RemoteStateReader for State {
+    fn get_storage_at(
+        &self,
+        _block_number: BlockNumber,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        Ok(self
+            .storage_of(&contract_address, &key)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn get_nonce_at(
+        &self,
+        _block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<Nonce> {
+        Ok(self
+            .nonce_of(&contract_address)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn get_class_hash_at(
+        &self,
+        _block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<ClassHash> {
+        Ok(self
+            .class_hash_of(&contract_address)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn get_contract_class(
+        &self,
+        _block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<ContractClass>> {
+        Ok(self.class(&class_hash).cloned())
+    }
+
+    fn get_compiled_class_hash(
+        &self,
+        _block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<CompiledClassHash>> {
+        Ok(self.compiled_class_hash_of(&class_hash).copied())
+    }
+}
+
+/// Values read from the remote, so that each entry is only fetched once.
+#[derive(Clone, Debug, Default)]
+struct RemoteCache {
+    storage: FxHashMap<ContractStorageKey, StarkFelt>,
+    nonces: FxHashMap<ContractAddress, Nonce>,
+    contracts: FxHashMap<ContractAddress, ClassHash>,
+    classes: FxHashMap<ClassHash, ContractClass>,
+    compiled_class_hashes: FxHashMap<ClassHash, CompiledClassHash>,
+}
+
+/// State forked from a remote chain at a pinned block. Reads hit the local
+/// writes first, then the cache of the remote reads, and only then the
+/// remote. Writes never reach the remote: they are kept in an overlay, which
+/// holds all the changes made on top of the fork.
+#[derive(Debug)]
+pub struct ForkedState<R> {
+    remote: R,
+    block_number: BlockNumber,
+    cache: RemoteCache,
+    overlay: State,
+}
+
+impl<R: RemoteStateReader> ForkedState<R> {
+    /// Creates a state forked from the remote at the provided block.
+    pub fn new(remote: R, block_number: BlockNumber) -> Self {
+        Self {
+            remote,
+            block_number,
+            cache: RemoteCache::default(),
+            overlay: State::default(),
+        }
+    }
+
+    /// Returns the block at which the remote is read.
+    pub const fn block_number(&self) -> BlockNumber {
+        self.block_number
+    }
+
+    /// Returns the remote reader.
+    pub const fn remote(&self) -> &R {
+        &self.remote
+    }
+
+    /// Returns the changes made on top of the fork.
+    pub const fn overlay(&self) -> &State {
+        &self.overlay
+    }
+
+    /// Drops the cache of the remote reads.
+    pub fn clear_cache(&mut self) {
+        self.cache = RemoteCache::default();
+    }
+}
+
+impl<R: RemoteStateReader> Committer<ForkedState<R>> for &mut ForkedState<R> {}
+
+impl<R: RemoteStateReader> BlockifierState for &mut ForkedState<R> {
+    fn set_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+        value: StarkFelt,
+    ) {
+        (&mut self.overlay).set_storage_at(contract_address, key, value);
+    }
+
+    /// # Errors
+    ///
+    /// If the nonce overflows or fails to be read from the remote.
+    fn increment_nonce(&mut self, contract_address: ContractAddress) -> StateResult<()> {
+        let current_nonce = self.get_nonce_at(contract_address)?;
+
+        let mut current_nonce: u64 = current_nonce.0.try_into()?;
+        if current_nonce == u64::MAX {
+            return Err(StateError::StateReadError("Nonce overflow".into()));
+        }
+        current_nonce += 1;
+
+        self.overlay
+            .set_nonce(contract_address, Nonce(StarkFelt::from(current_nonce)));
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// If the contract address is linked to a class hash, locally or on the
+    /// remote.
+    fn set_class_hash_at(
+        &mut self,
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+    ) -> StateResult<()> {
+        if self.get_class_hash_at(contract_address)? != ClassHash::default() {
+            return Err(StateError::UnavailableContractAddress(contract_address));
+        }
+        (&mut self.overlay).set_class_hash_at(contract_address, class_hash)
+    }
+
+    fn set_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+        contract_class: ContractClass,
+    ) -> StateResult<()> {
+        (&mut self.overlay).set_contract_class(class_hash, contract_class)
+    }
+
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: ClassHash,
+        compiled_class_hash: CompiledClassHash,
+    ) -> StateResult<()> {
+        (&mut self.overlay).set_compiled_class_hash(class_hash, compiled_class_hash)
+    }
+
+    fn to_state_diff(&mut self) -> CommitmentStateDiff {
+        unreachable!("to_state_diff should not be called in the sequencer")
+    }
+}
+
+impl<R: RemoteStateReader> BlockifierStateReader for &mut ForkedState<R> {
+    fn get_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        if let Some(value) = self.overlay.storage_of(&contract_address, &key) {
+            return Ok(*value);
+        }
+        if let Some(value) = self.cache.storage.get(&(contract_address, key)) {
+            return Ok(*value);
+        }
+        let value = self
+            .remote
+            .get_storage_at(self.block_number, contract_address, key)?;
+        self.cache.storage.insert((contract_address, key), value);
+        Ok(value)
+    }
+
+    fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        if let Some(nonce) = self.overlay.nonce_of(&contract_address) {
+            return Ok(*nonce);
+        }
+        if let Some(nonce) = self.cache.nonces.get(&contract_address) {
+            return Ok(*nonce);
+        }
+        let nonce = self
+            .remote
+            .get_nonce_at(self.block_number, contract_address)?;
+        self.cache.nonces.insert(contract_address, nonce);
+        Ok(nonce)
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        if let Some(class_hash) = self.overlay.class_hash_of(&contract_address) {
+            return Ok(*class_hash);
+        }
+        if let Some(class_hash) = self.cache.contracts.get(&contract_address) {
+            return Ok(*class_hash);
+        }
+        let class_hash = self
+            .remote
+            .get_class_hash_at(self.block_number, contract_address)?;
+        self.cache.contracts.insert(contract_address, class_hash);
+        Ok(class_hash)
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class is declared neither locally nor on the remote.
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> StateResult<ContractClass> {
+        if let Some(class) = self.overlay.class(class_hash) {
+            return Ok(class.clone());
+        }
+        if let Some(class) = self.cache.classes.get(class_hash) {
+            return Ok(class.clone());
+        }
+        let class = self
+            .remote
+            .get_contract_class(self.block_number, *class_hash)?
+            .ok_or(StateError::UndeclaredClassHash(*class_hash))?;
+        self.cache.classes.insert(*class_hash, class.clone());
+        Ok(class)
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class hash is declared neither locally nor on the
+    /// remote.
+    fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        if let Some(compiled_class_hash) = self.overlay.compiled_class_hash_of(&class_hash) {
+            return Ok(*compiled_class_hash);
+        }
+        if let Some(compiled_class_hash) = self.cache.compiled_class_hashes.get(&class_hash) {
+            return Ok(*compiled_class_hash);
+        }
+        let compiled_class_hash = self
+            .remote
+            .get_compiled_class_hash(self.block_number, class_hash)?
+            .ok_or(StateError::UndeclaredClassHash(class_hash))?;
+        self.cache
+            .compiled_class_hashes
+            .insert(class_hash, compiled_class_hash);
+        Ok(compiled_class_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use blockifier::execution::contract_class::ContractClassV0;
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT, TEST_CONTRACT, TWO_CLASS_HASH,
+        TWO_FELT, TWO_PATRICIA,
+    };
+
+    use super::*;
+
+    /// Remote backed by a local state, which counts the storage reads.
+    struct CountingRemote {
+        state: State,
+        storage_reads: Cell<usize>,
+    }
+
+    impl RemoteStateReader for CountingRemote {
+        fn get_storage_at(
+            &self,
+            block_number: BlockNumber,
+            contract_address: ContractAddress,
+            key: StorageKey,
+        ) -> StateResult<StarkFelt> {
+            self.storage_reads.set(self.storage_reads.get() + 1);
+            RemoteStateReader::get_storage_at(&self.state, block_number, contract_address, key)
+        }
+
+        fn get_nonce_at(
+            &self,
+            block_number: BlockNumber,
+            contract_address: ContractAddress,
+        ) -> StateResult<Nonce> {
+            RemoteStateReader::get_nonce_at(&self.state, block_number, contract_address)
+        }
+
+        fn get_class_hash_at(
+            &self,
+            block_number: BlockNumber,
+            contract_address: ContractAddress,
+        ) -> StateResult<ClassHash> {
+            RemoteStateReader::get_class_hash_at(&self.state, block_number, contract_address)
+        }
+
+        fn get_contract_class(
+            &self,
+            block_number: BlockNumber,
+            class_hash: ClassHash,
+        ) -> StateResult<Option<ContractClass>> {
+            RemoteStateReader::get_contract_class(&self.state, block_number, class_hash)
+        }
+
+        fn get_compiled_class_hash(
+            &self,
+            block_number: BlockNumber,
+            class_hash: ClassHash,
+        ) -> StateResult<Option<CompiledClassHash>> {
+            RemoteStateReader::get_compiled_class_hash(&self.state, block_number, class_hash)
+        }
+    }
+
+    fn remote() -> CountingRemote {
+        let mut state = State::default();
+        (&mut state)
+            .set_contract_class(
+                &ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.set_nonce(*TEST_CONTRACT, Nonce(*ONE_FELT));
+
+        CountingRemote {
+            state,
+            storage_reads: Cell::new(0),
+        }
+    }
+
+    #[test]
+    fn test_forked_state_caches_remote_reads() {
+        // Given
+        let mut forked = ForkedState::new(remote(), BlockNumber(1));
+        let mut state = &mut forked;
+
+        // When
+        let first = state
+            .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+            .unwrap();
+        let second = state
+            .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+            .unwrap();
+
+        // Then
+        assert_eq!(first, *ONE_FELT);
+        assert_eq!(second, *ONE_FELT);
+        assert_eq!(forked.remote().storage_reads.get(), 1);
+    }
+
+    #[test]
+    fn test_forked_state_writes_to_overlay() {
+        // Given
+        let mut forked = ForkedState::new(remote(), BlockNumber(1));
+        let mut state = &mut forked;
+
+        // When
+        state.set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        state.set_storage_at(*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *ONE_FELT);
+        state.increment_nonce(*TEST_CONTRACT).unwrap();
+
+        // Then
+        assert_eq!(
+            state
+                .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+                .unwrap(),
+            *TWO_FELT
+        );
+        assert_eq!(
+            state.get_nonce_at(*TEST_CONTRACT).unwrap(),
+            Nonce(*TWO_FELT)
+        );
+        assert_eq!(forked.remote().storage_reads.get(), 0);
+        assert_eq!(
+            forked
+                .remote()
+                .state
+                .storage_of(&TEST_CONTRACT, &StorageKey(*ONE_PATRICIA)),
+            Some(&*ONE_FELT)
+        );
+        assert_eq!(forked.overlay().statistics().storage_slots, 2);
+    }
+
+    #[test]
+    fn test_forked_state_remote_contract_is_unavailable() {
+        // Given
+        let mut forked = ForkedState::new(remote(), BlockNumber(1));
+        let mut state = &mut forked;
+
+        // When
+        let result = state.set_class_hash_at(*TEST_CONTRACT, *TWO_CLASS_HASH);
+        state
+            .set_class_hash_at(*TEST_ACCOUNT, *ONE_CLASS_HASH)
+            .unwrap();
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(StateError::UnavailableContractAddress(_))
+        ));
+        assert!(state.get_compiled_contract_class(&ONE_CLASS_HASH).is_ok());
+        assert!(matches!(
+            state.get_compiled_contract_class(&TWO_CLASS_HASH),
+            Err(StateError::UndeclaredClassHash(_))
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use blockifier::execution::contract_class::ContractClass;
+use blockifier::state::errors::StateError;
+use blockifier::state::state_api::StateResult;
+use starknet::core::types::{
+    BlockId, ContractClass as RpcContractClass, FieldElement, StarknetError,
+};
+use starknet::providers::{
+    jsonrpc::{HttpTransport, JsonRpcClient},
+    MaybeUnknownErrorCode, Provider, ProviderError, StarknetErrorWithMessage,
+};
+use starknet_api::{
+    block::BlockNumber,
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+use tokio::runtime::Runtime;
+use url::Url;
+
+use super::RemoteStateReader;
+use crate::transaction::{flattened_sierra_to_contract_class, legacy_to_contract_class};
+
+/// Remote state reader backed by a Starknet JSON-RPC node. Requests are run
+/// on a dedicated single-threaded runtime, so the reader can be used from
+/// synchronous code. Sierra classes are compiled to CASM on load, which is
+/// also how their compiled class hash is obtained.
+pub struct RpcStateReader {
+    provider: JsonRpcClient<HttpTransport>,
+    runtime: Runtime,
+}
+
+impl RpcStateReader {
+    /// Creates a reader for the node at the provided URL.
+    ///
+    /// # Errors
+    ///
+    /// If the runtime fails to start.
+    pub fn new(url: Url) -> std::io::Result<Self> {
+        Ok(Self {
+            provider: JsonRpcClient::new(HttpTransport::new(url)),
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+        })
+    }
+
+    fn fetch_class(
+        &self,
+        block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<ContractClass>> {
+        let class = self.runtime.block_on(self.provider.get_class(
+            BlockId::Number(block_number.0),
+            to_field_element(&class_hash.0)?,
+        ));
+        let class = match class {
+            Ok(class) => class,
+            Err(error) if is_not_found(&error) => return Ok(None),
+            Err(error) => return Err(to_state_error(error)),
+        };
+
+        Ok(Some(match class {
+            RpcContractClass::Legacy(class) => {
+                legacy_to_contract_class(&class).map_err(to_state_error)?
+            }
+            RpcContractClass::Sierra(class) => {
+                flattened_sierra_to_contract_class(&class)
+                    .map_err(to_state_error)?
+                    .0
+            }
+        }))
+    }
+}
+
+impl RemoteStateReader for RpcStateReader {
+    fn get_storage_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        let value = self.runtime.block_on(self.provider.get_storage_at(
+            to_field_element(contract_address.0.key())?,
+            to_field_element(key.0.key())?,
+            BlockId::Number(block_number.0),
+        ));
+        or_default(value)
+    }
+
+    fn get_nonce_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<Nonce> {
+        let nonce = self.runtime.block_on(self.provider.get_nonce(
+            BlockId::Number(block_number.0),
+            to_field_element(contract_address.0.key())?,
+        ));
+        or_default(nonce).map(Nonce)
+    }
+
+    fn get_class_hash_at(
+        &self,
+        block_number: BlockNumber,
+        contract_address: ContractAddress,
+    ) -> StateResult<ClassHash> {
+        let class_hash = self.runtime.block_on(self.provider.get_class_hash_at(
+            BlockId::Number(block_number.0),
+            to_field_element(contract_address.0.key())?,
+        ));
+        or_default(class_hash).map(ClassHash)
+    }
+
+    fn get_contract_class(
+        &self,
+        block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<ContractClass>> {
+        self.fetch_class(block_number, class_hash)
+    }
+
+    /// The compiled class hash isn't exposed by the RPC: it's computed by
+    /// compiling the Sierra class. Cairo 0 classes have no compiled class hash.
+    fn get_compiled_class_hash(
+        &self,
+        block_number: BlockNumber,
+        class_hash: ClassHash,
+    ) -> StateResult<Option<CompiledClassHash>> {
+        let class = self.runtime.block_on(self.provider.get_class(
+            BlockId::Number(block_number.0),
+            to_field_element(&class_hash.0)?,
+        ));
+        match class {
+            Ok(RpcContractClass::Sierra(class)) => Ok(Some(
+                flattened_sierra_to_contract_class(&class)
+                    .map_err(to_state_error)?
+                    .1,
+            )),
+            Ok(RpcContractClass::Legacy(_)) => Ok(None),
+            Err(error) if is_not_found(&error) => Ok(None),
+            Err(error) => Err(to_state_error(error)),
+        }
+    }
+}
+
+fn is_not_found(error: &ProviderError) -> bool {
+    matches!(
+        error,
+        ProviderError::StarknetError(StarknetErrorWithMessage {
+            code: MaybeUnknownErrorCode::Known(
+                StarknetError::ContractNotFound | StarknetError::ClassHashNotFound
+            ),
+            ..
+        })
+    )
+}
+
+/// Reads missing contracts as zero, like the local state does.
+fn or_default(value: Result<FieldElement, ProviderError>) -> StateResult<StarkFelt> {
+    match value {
+        Ok(value) => Ok(value.into()),
+        Err(error) if is_not_found(&error) => Ok(StarkFelt::default()),
+        Err(error) => Err(to_state_error(error)),
+    }
+}
+
+fn to_field_element(felt: &StarkFelt) -> StateResult<FieldElement> {
+    FieldElement::from_bytes_be(felt.bytes())
+        .map_err(|_| StateError::StateReadError(format!("invalid field element {}", felt)))
+}
+
+fn to_state_error(error: impl std::fmt::Display) -> StateError {
+    StateError::StateReadError(error.to_string())
+}
@@ -0,0 +1,563 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use blockifier::{
+    execution::contract_class::ContractClass,
+    state::state_api::{State as BlockifierState, StateReader as BlockifierStateReader},
+    transaction::{
+        account_transaction::AccountTransaction,
+        errors::TransactionExecutionError,
+        objects::{TransactionExecutionInfo, TransactionExecutionResult},
+        transaction_execution::Transaction,
+        transactions::{
+            DeclareTransaction, DeployAccountTransaction, InvokeTransaction, L1HandlerTransaction,
+        },
+    },
+};
+use serde::{Deserialize, Serialize};
+use starknet_api::{
+    core::ContractAddress,
+    transaction::{
+        DeclareTransaction as ApiDeclareTransaction,
+        DeployAccountTransaction as ApiDeployAccountTransaction, Fee,
+        InvokeTransaction as ApiInvokeTransaction, L1HandlerTransaction as ApiL1HandlerTransaction,
+        TransactionHash,
+    },
+};
+use thiserror::Error;
+
+use crate::{
+    block_context::SerializableBlockContext,
+    commit::Committer,
+    execution::Execution,
+    sequencer::{sender_address, Sequencer},
+    serde::{canonical::CanonicalState, DumpLoad, SerializationError},
+    state::State,
+};
+
+/// Current version of the journal format.
+pub const JOURNAL_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    SerializationError(#[from] SerializationError),
+    #[error(transparent)]
+    TransactionExecutionError(#[from] TransactionExecutionError),
+    #[error("invalid journal: {0}")]
+    InvalidJournal(String),
+    #[error("state hash mismatch at record {record}: expected {expected}, got {actual}")]
+    StateMismatch {
+        record: usize,
+        expected: String,
+        actual: String,
+    },
+    #[error("outcome mismatch at record {record}: expected {expected:?}, got {actual:?}")]
+    OutcomeMismatch {
+        record: usize,
+        expected: JournalOutcome,
+        actual: JournalOutcome,
+    },
+}
+
+/// Serializable representation of a transaction, holding everything needed
+/// to execute it again.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalTransaction {
+    Invoke {
+        tx: ApiInvokeTransaction,
+        tx_hash: TransactionHash,
+    },
+    Declare {
+        tx: ApiDeclareTransaction,
+        tx_hash: TransactionHash,
+        contract_class: ContractClass,
+    },
+    DeployAccount {
+        tx: ApiDeployAccountTransaction,
+        tx_hash: TransactionHash,
+        contract_address: ContractAddress,
+    },
+    L1Handler {
+        tx: ApiL1HandlerTransaction,
+        tx_hash: TransactionHash,
+        paid_fee_on_l1: Fee,
+    },
+}
+
+impl From<&Transaction> for JournalTransaction {
+    fn from(transaction: &Transaction) -> Self {
+        match transaction {
+            Transaction::AccountTransaction(AccountTransaction::Invoke(tx)) => Self::Invoke {
+                tx: tx.tx.clone(),
+                tx_hash: tx.tx_hash,
+            },
+            Transaction::AccountTransaction(AccountTransaction::Declare(tx)) => Self::Declare {
+                tx: tx.tx().clone(),
+                tx_hash: tx.tx_hash(),
+                contract_class: tx.contract_class(),
+            },
+            Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+                Self::DeployAccount {
+                    tx: tx.tx.clone(),
+                    tx_hash: tx.tx_hash,
+                    contract_address: tx.contract_address,
+                }
+            }
+            Transaction::L1HandlerTransaction(tx) => Self::L1Handler {
+                tx: tx.tx.clone(),
+                tx_hash: tx.tx_hash,
+                paid_fee_on_l1: tx.paid_fee_on_l1,
+            },
+        }
+    }
+}
+
+impl TryFrom<JournalTransaction> for Transaction {
+    type Error = TransactionExecutionError;
+
+    fn try_from(transaction: JournalTransaction) -> Result<Self, Self::Error> {
+        Ok(match transaction {
+            JournalTransaction::Invoke { tx, tx_hash } => {
+                Self::AccountTransaction(AccountTransaction::Invoke(InvokeTransaction {
+                    tx,
+                    tx_hash,
+                    only_query: false,
+                }))
+            }
+            JournalTransaction::Declare {
+                tx,
+                tx_hash,
+                contract_class,
+            } => Self::AccountTransaction(AccountTransaction::Declare(DeclareTransaction::new(
+                tx,
+                tx_hash,
+                contract_class,
+            )?)),
+            JournalTransaction::DeployAccount {
+                tx,
+                tx_hash,
+                contract_address,
+            } => Self::AccountTransaction(AccountTransaction::DeployAccount(
+                DeployAccountTransaction {
+                    tx,
+                    tx_hash,
+                    contract_address,
+                    only_query: false,
+                },
+            )),
+            JournalTransaction::L1Handler {
+                tx,
+                tx_hash,
+                paid_fee_on_l1,
+            } => Self::L1HandlerTransaction(L1HandlerTransaction {
+                tx,
+                tx_hash,
+                paid_fee_on_l1,
+            }),
+        })
+    }
+}
+
+/// Outcome of the execution of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JournalOutcome {
+    Succeeded {
+        actual_fee: Fee,
+    },
+    Reverted {
+        actual_fee: Fee,
+        revert_error: String,
+    },
+    Rejected {
+        error: String,
+    },
+}
+
+impl From<&TransactionExecutionResult<TransactionExecutionInfo>> for JournalOutcome {
+    fn from(result: &TransactionExecutionResult<TransactionExecutionInfo>) -> Self {
+        match result {
+            Ok(info) => match &info.revert_error {
+                Some(revert_error) => Self::Reverted {
+                    actual_fee: info.actual_fee,
+                    revert_error: revert_error.clone(),
+                },
+                None => Self::Succeeded {
+                    actual_fee: info.actual_fee,
+                },
+            },
+            Err(error) => Self::Rejected {
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Executed transaction, along with the block context it ran under and its
+/// outcome. `validate` is false if the sender was impersonated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub block_context: SerializableBlockContext,
+    pub validate: bool,
+    pub transaction: JournalTransaction,
+    pub outcome: JournalOutcome,
+}
+
+/// Record of a journal, stored as one JSON object per line. A journal starts
+/// with a header holding the content hash of the base state, followed by the
+/// executed transactions. Checkpoints hold the content hash of the state at
+/// the time they were recorded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum JournalRecord {
+    Header {
+        version: u32,
+        base_state_hash: String,
+    },
+    Transaction(Box<JournalEntry>),
+    Checkpoint {
+        state_hash: String,
+    },
+}
+
+/// Records the transactions executed on a sequencer to a journal.
+pub struct JournalWriter<W: Write> {
+    writer: W,
+}
+
+impl JournalWriter<BufWriter<File>> {
+    /// Creates the journal file, recording the base state.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be created or the header fails to be written.
+    pub fn create(path: &Path, base: &State) -> Result<Self, JournalError> {
+        Self::new(BufWriter::new(File::create(path)?), base)
+    }
+}
+
+impl<W: Write> JournalWriter<W> {
+    /// Creates a journal writing to the writer, recording the base state.
+    ///
+    /// # Errors
+    ///
+    /// If the header fails to be written.
+    pub fn new(writer: W, base: &State) -> Result<Self, JournalError> {
+        let mut journal = Self { writer };
+        journal.write(&JournalRecord::Header {
+            version: JOURNAL_VERSION,
+            base_state_hash: CanonicalState::from(base).content_hash()?,
+        })?;
+        Ok(journal)
+    }
+
+    /// Executes the transaction on the sequencer and records it, along with
+    /// the block context and the outcome of the execution. The result of the
+    /// execution is returned as is, whether the transaction was accepted or
+    /// not.
+    ///
+    /// # Errors
+    ///
+    /// If the record fails to be written.
+    pub fn execute<S, A>(
+        &mut self,
+        sequencer: &mut Sequencer<S, A>,
+        transaction: Transaction,
+    ) -> Result<TransactionExecutionResult<TransactionExecutionInfo>, JournalError>
+    where
+        for<'any> &'any mut S: BlockifierState + BlockifierStateReader + Committer<S>,
+    {
+        let block_context = SerializableBlockContext::from(sequencer.block_context());
+        let validate = !sequencer.is_impersonated(&sender_address(&transaction));
+        let journal_transaction = JournalTransaction::from(&transaction);
+
+        let result = sequencer.execute(transaction);
+
+        self.write(&JournalRecord::Transaction(Box::new(JournalEntry {
+            block_context,
+            validate,
+            transaction: journal_transaction,
+            outcome: JournalOutcome::from(&result),
+        })))?;
+        Ok(result)
+    }
+
+    /// Records the content hash of the state, which is checked on replay.
+    ///
+    /// # Errors
+    ///
+    /// If the record fails to be written.
+    pub fn checkpoint(&mut self, state: &State) -> Result<(), JournalError> {
+        self.write(&JournalRecord::Checkpoint {
+            state_hash: CanonicalState::from(state).content_hash()?,
+        })
+    }
+
+    /// Records a final checkpoint of the state, so that a replay checks the
+    /// state reached at the end of the journal, then returns the underlying
+    /// writer.
+    ///
+    /// # Errors
+    ///
+    /// If the checkpoint fails to be written.
+    pub fn finish(mut self, state: &State) -> Result<W, JournalError> {
+        self.checkpoint(state)?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, record: &JournalRecord) -> Result<(), JournalError> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Replays a journal on top of the base state. Each transaction is executed
+/// under its recorded block context, and its outcome as well as each
+/// checkpoint are checked against the journal. Returns the final state.
+///
+/// # Errors
+///
+/// If the journal is invalid, if the base state isn't the one the journal was
+/// recorded on, or if an outcome or a checkpoint doesn't match.
+pub fn replay_journal(reader: impl BufRead, base: State) -> Result<State, JournalError> {
+    let mut lines = reader.lines();
+
+    let header = lines
+        .next()
+        .ok_or_else(|| JournalError::InvalidJournal(String::from("empty journal")))??;
+    let base_state_hash = match serde_json::from_str(&header)? {
+        JournalRecord::Header {
+            version,
+            base_state_hash,
+        } if version == JOURNAL_VERSION => base_state_hash,
+        JournalRecord::Header { version, .. } => {
+            return Err(JournalError::InvalidJournal(format!(
+                "unsupported journal version {}",
+                version
+            )))
+        }
+        _ => {
+            return Err(JournalError::InvalidJournal(String::from(
+                "missing journal header",
+            )))
+        }
+    };
+    check_state_hash(0, &base_state_hash, &base)?;
+
+    let mut base = Some(base);
+    let mut sequencer: Option<Sequencer<State, ()>> = None;
+
+    for (index, line) in lines.enumerate() {
+        let record = index + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line)? {
+            JournalRecord::Header { .. } => {
+                return Err(JournalError::InvalidJournal(format!(
+                    "unexpected header at record {}",
+                    record
+                )))
+            }
+            JournalRecord::Checkpoint { state_hash } => {
+                let state = match (&sequencer, &base) {
+                    (Some(sequencer), _) => &sequencer.state,
+                    (None, Some(base)) => base,
+                    (None, None) => unreachable!("the state is either the base or the sequencer's"),
+                };
+                check_state_hash(record, &state_hash, state)?;
+            }
+            JournalRecord::Transaction(entry) => {
+                let JournalEntry {
+                    block_context,
+                    validate,
+                    transaction,
+                    outcome,
+                } = *entry;
+
+                let sequencer = match &mut sequencer {
+                    Some(sequencer) => {
+                        sequencer.set_block_context(block_context.into());
+                        sequencer
+                    }
+                    None => sequencer.insert(Sequencer::new(
+                        block_context.into(),
+                        base.take().unwrap_or_default(),
+                        (),
+                    )),
+                };
+
+                let transaction = Transaction::try_from(transaction)?;
+                let sender = sender_address(&transaction);
+                if !validate {
+                    sequencer.impersonate_account(sender);
+                }
+                let result = sequencer.execute(transaction);
+                sequencer.stop_impersonating_account(sender);
+
+                let actual = JournalOutcome::from(&result);
+                if actual != outcome {
+                    return Err(JournalError::OutcomeMismatch {
+                        record,
+                        expected: outcome,
+                        actual,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(match (sequencer, base) {
+        (Some(sequencer), _) => sequencer.state,
+        (None, base) => base.unwrap_or_default(),
+    })
+}
+
+/// Replays the journal file on top of the base state dump.
+///
+/// # Errors
+///
+/// If a file fails to load or if the replay fails.
+pub fn replay_journal_file(
+    journal_path: &Path,
+    base_dump_path: &Path,
+) -> Result<State, JournalError> {
+    let base = State::load_state_from_file(base_dump_path)?;
+    replay_journal(BufReader::new(File::open(journal_path)?), base)
+}
+
+fn check_state_hash(record: usize, expected: &str, state: &State) -> Result<(), JournalError> {
+    let actual = CanonicalState::from(state).content_hash()?;
+    if actual != expected {
+        return Err(JournalError::StateMismatch {
+            record,
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::abi::abi_utils::get_storage_var_address;
+    use starknet_api::hash::StarkFelt;
+
+    use crate::constants::test_constants::{
+        block_context, counter_sequencer, increment, ONE_BLOCK_NUMBER, TEST_CONTRACT,
+    };
+
+    use super::*;
+
+    fn base_state() -> State {
+        counter_sequencer().state
+    }
+
+    fn record_journal() -> (Vec<u8>, State) {
+        let base = base_state();
+        let mut journal = JournalWriter::new(Vec::new(), &base).unwrap();
+        let mut sequencer = Sequencer::new(block_context(), base, ());
+
+        journal
+            .execute(&mut sequencer, increment(0))
+            .unwrap()
+            .unwrap();
+        sequencer.mine_block().unwrap();
+        journal
+            .execute(&mut sequencer, increment(1))
+            .unwrap()
+            .unwrap();
+
+        (journal.finish(&sequencer.state).unwrap(), sequencer.state)
+    }
+
+    #[test]
+    fn test_record_and_replay_journal() {
+        // Given
+        let (journal, state) = record_journal();
+
+        // When
+        let replayed = replay_journal(journal.as_slice(), base_state()).unwrap();
+
+        // Then
+        assert_eq!(replayed, state);
+        let counter = replayed
+            .storage_of(&TEST_CONTRACT, &get_storage_var_address("counter", &[]))
+            .copied();
+        assert_eq!(counter, Some(StarkFelt::from(2u8)));
+    }
+
+    #[test]
+    fn test_replay_journal_records_block_context() {
+        // Given
+        let (journal, _) = record_journal();
+
+        // When
+        let records: Vec<JournalRecord> = journal
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+
+        // Then
+        assert_eq!(records.len(), 4);
+        let JournalRecord::Transaction(entry) = &records[2] else {
+            panic!("expected a transaction record");
+        };
+        assert_eq!(entry.block_context.block_number.0, ONE_BLOCK_NUMBER.0 + 1);
+        assert!(matches!(entry.outcome, JournalOutcome::Succeeded { .. }));
+        assert!(matches!(records[3], JournalRecord::Checkpoint { .. }));
+    }
+
+    #[test]
+    fn test_replay_journal_wrong_base() {
+        // Given
+        let (journal, _) = record_journal();
+
+        // When
+        let result = replay_journal(journal.as_slice(), State::default());
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(JournalError::StateMismatch { record: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_replay_journal_outcome_mismatch() {
+        // Given
+        let (journal, _) = record_journal();
+        let journal = String::from_utf8(journal).unwrap();
+        let mut lines: Vec<String> = journal.lines().map(String::from).collect();
+        let mut record: JournalRecord = serde_json::from_str(&lines[1]).unwrap();
+        if let JournalRecord::Transaction(entry) = &mut record {
+            entry.outcome = JournalOutcome::Rejected {
+                error: String::from("rejected"),
+            };
+        }
+        lines[1] = serde_json::to_string(&record).unwrap();
+
+        // When
+        let result = replay_journal(lines.join("\n").as_bytes(), base_state());
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(JournalError::OutcomeMismatch { record: 1, .. })
+        ));
+    }
+}
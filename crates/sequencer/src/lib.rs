@@ -1,7 +1,23 @@
+pub mod account;
+#[cfg(feature = "async")]
+pub mod async_sequencer;
+pub mod block_context;
+pub mod class_registry;
 pub mod commit;
+pub mod config;
 pub mod constants;
+pub mod diff;
 pub mod execution;
+pub mod fork;
+pub mod journal;
+pub mod metrics;
+pub mod observer;
+pub mod persistent;
+pub mod receipt;
+pub mod scenario;
 pub mod sequencer;
 pub mod serde;
+pub mod shared;
+pub mod sierra;
 pub mod state;
 pub mod transaction;
@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use blockifier::state::cached_state::ContractStorageKey;
+use rustc_hash::FxHashMap;
+use starknet_api::core::ContractAddress;
+
+/// Number of transactions reading and writing a storage slot or a contract.
+/// See [`StorageMetrics`] for what counts as an access.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StorageAccess {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl StorageAccess {
+    /// Returns the total number of accesses.
+    pub const fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.reads += other.reads;
+        self.writes += other.writes;
+    }
+}
+
+/// Storage accesses of the executed transactions, per contract and storage
+/// key.
+///
+/// Reads are recorded at the level of the sequencer state, below the cached
+/// state of blockifier, which serves the repeated reads of a transaction:
+/// `reads` counts the first read of the slot by each transaction, not every
+/// access. Writes are recorded when a transaction is committed: `writes`
+/// counts the transactions which changed the slot. Reverted and rejected
+/// transactions only record their reads. Writes made directly on the state,
+/// such as the setup helpers of the sequencer, and simulations and calls,
+/// executed on a read-only view of the state, aren't recorded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageMetrics {
+    accesses: FxHashMap<ContractStorageKey, StorageAccess>,
+    new_slots: usize,
+}
+
+impl StorageMetrics {
+    pub(crate) fn record_read(&mut self, key: ContractStorageKey) {
+        self.accesses.entry(key).or_default().reads += 1;
+    }
+
+    pub(crate) fn record_write(&mut self, key: ContractStorageKey, is_new_slot: bool) {
+        self.accesses.entry(key).or_default().writes += 1;
+        if is_new_slot {
+            self.new_slots += 1;
+        }
+    }
+
+    /// Returns the accesses of the storage slot.
+    pub fn slot(&self, key: &ContractStorageKey) -> StorageAccess {
+        self.accesses.get(key).copied().unwrap_or_default()
+    }
+
+    /// Returns an iterator over the accesses of each storage slot.
+    pub fn slots(&self) -> impl Iterator<Item = (&ContractStorageKey, &StorageAccess)> {
+        self.accesses.iter()
+    }
+
+    /// Returns the accesses aggregated per contract.
+    pub fn contracts(&self) -> FxHashMap<ContractAddress, StorageAccess> {
+        let mut contracts = FxHashMap::<_, StorageAccess>::default();
+        for ((address, _), access) in &self.accesses {
+            contracts.entry(*address).or_default().add(access);
+        }
+        contracts
+    }
+
+    /// Returns the accesses over all storage slots.
+    pub fn total(&self) -> StorageAccess {
+        let mut total = StorageAccess::default();
+        for access in self.accesses.values() {
+            total.add(access);
+        }
+        total
+    }
+
+    /// Returns the `n` most accessed storage slots, the most accessed first.
+    /// Ties are broken by contract address then storage key.
+    pub fn hot_keys(&self, n: usize) -> Vec<(ContractStorageKey, StorageAccess)> {
+        let mut slots: Vec<_> = self
+            .accesses
+            .iter()
+            .map(|(key, access)| (*key, *access))
+            .collect();
+        slots.sort_by(|(key, access), (other_key, other_access)| {
+            other_access
+                .total()
+                .cmp(&access.total())
+                .then_with(|| key.cmp(other_key))
+        });
+        slots.truncate(n);
+        slots
+    }
+
+    /// Returns the number of slots written while absent from the state.
+    pub const fn new_slots(&self) -> usize {
+        self.new_slots
+    }
+
+    /// Returns true if no access was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.accesses.is_empty()
+    }
+}
+
+/// Storage metrics shared between a state and its owner, which reads them
+/// back. Cloning the handle doesn't clone the metrics.
+#[derive(Clone, Debug, Default)]
+pub struct SharedStorageMetrics(Arc<Mutex<StorageMetrics>>);
+
+impl SharedStorageMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the metrics. A poisoned lock is recovered, since recording an
+    /// access can't leave the metrics inconsistent.
+    pub fn lock(&self) -> MutexGuard<'_, StorageMetrics> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Returns a copy of the metrics recorded so far.
+    pub fn snapshot(&self) -> StorageMetrics {
+        self.lock().clone()
+    }
+
+    /// Returns the metrics recorded so far and resets them. Calling it after
+    /// each transaction gives the metrics per transaction.
+    pub fn take(&self) -> StorageMetrics {
+        std::mem::take(&mut *self.lock())
+    }
+}
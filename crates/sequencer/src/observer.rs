@@ -0,0 +1,35 @@
+use blockifier::{
+    block_context::BlockContext,
+    state::cached_state::CommitmentStateDiff,
+    transaction::{
+        errors::TransactionExecutionError, objects::TransactionExecutionInfo,
+        transaction_execution::Transaction,
+    },
+};
+
+/// Hooks called by the sequencer along the lifecycle of transactions and
+/// blocks. All callbacks default to doing nothing, so that observers only
+/// implement the ones they need.
+///
+/// Observers are shared between the clones of a sequencer, hence the callbacks
+/// take `&self`: observers holding data should use interior mutability.
+pub trait ExecutionObserver: Send + Sync {
+    /// Called before the transaction is executed, with the context of the
+    /// block it executes in.
+    fn before_transaction(&self, _transaction: &Transaction, _block_context: &BlockContext) {}
+
+    /// Called after a successful transaction, with the diff committed to
+    /// the state.
+    fn after_transaction(&self, _info: &TransactionExecutionInfo, _diff: &CommitmentStateDiff) {}
+
+    /// Called after a reverted transaction. Only the nonce of the sender is
+    /// committed to the state.
+    fn on_revert(&self, _info: &TransactionExecutionInfo) {}
+
+    /// Called when the transaction is rejected. Nothing is committed to the
+    /// state.
+    fn on_rejection(&self, _error: &TransactionExecutionError) {}
+
+    /// Called when a block is closed, with the context of the closed block.
+    fn on_block_close(&self, _block_context: &BlockContext) {}
+}
@@ -0,0 +1,702 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use blockifier::state::cached_state::{CommitmentStateDiff, ContractStorageKey};
+use blockifier::state::errors::StateError;
+use blockifier::state::state_api::{
+    State as BlockifierState, StateReader as BlockifierStateReader, StateResult,
+};
+use blockifier::{block_context::BlockContext, execution::contract_class::ContractClass};
+use rustc_hash::FxHashMap;
+use sha2::{Digest, Sha256};
+use starknet_api::{
+    block::{BlockNumber, BlockTimestamp},
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    state::StorageKey,
+    StarknetApiError,
+};
+use thiserror::Error;
+
+use crate::{block_context::BlockContextError, commit::Committer};
+
+/// Magic bytes at the start of the log.
+const MAGIC: &[u8; 4] = b"SQLG";
+/// Current version of the log format.
+const VERSION: u8 = 1;
+/// Length of the log header: magic and version.
+const HEADER_LEN: u64 = 5;
+/// Length of a batch header: payload length and SHA-256 of the payload.
+const BATCH_HEADER_LEN: u64 = 40;
+
+#[derive(Error, Debug)]
+pub enum PersistentStateError {
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    StarknetApiError(#[from] StarknetApiError),
+    #[error(transparent)]
+    BlockContextError(#[from] BlockContextError),
+    #[error("corrupted log: {0}")]
+    Corrupted(String),
+}
+
+/// Key of an entry of the state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Class(ClassHash),
+    CompiledClassHash(ClassHash),
+    Contract(ContractAddress),
+    Storage(ContractStorageKey),
+    Nonce(ContractAddress),
+}
+
+impl Key {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Class(class_hash) => {
+                buf.push(0);
+                buf.extend_from_slice(class_hash.0.bytes());
+            }
+            Self::CompiledClassHash(class_hash) => {
+                buf.push(1);
+                buf.extend_from_slice(class_hash.0.bytes());
+            }
+            Self::Contract(address) => {
+                buf.push(2);
+                buf.extend_from_slice(address.0.key().bytes());
+            }
+            Self::Storage((address, key)) => {
+                buf.push(3);
+                buf.extend_from_slice(address.0.key().bytes());
+                buf.extend_from_slice(key.0.key().bytes());
+            }
+            Self::Nonce(address) => {
+                buf.push(4);
+                buf.extend_from_slice(address.0.key().bytes());
+            }
+        }
+    }
+
+    fn decode(reader: &mut impl Read) -> Result<Self, PersistentStateError> {
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+        Ok(match tag[0] {
+            0 => Self::Class(ClassHash(read_felt(reader)?)),
+            1 => Self::CompiledClassHash(ClassHash(read_felt(reader)?)),
+            2 => Self::Contract(read_address(reader)?),
+            3 => Self::Storage((
+                read_address(reader)?,
+                StorageKey(PatriciaKey::try_from(read_felt(reader)?)?),
+            )),
+            4 => Self::Nonce(read_address(reader)?),
+            tag => {
+                return Err(PersistentStateError::Corrupted(format!(
+                    "unknown record tag {}",
+                    tag
+                )))
+            }
+        })
+    }
+}
+
+/// Location of a committed value in the log.
+#[derive(Clone, Copy, Debug)]
+struct Location {
+    offset: u64,
+    len: u64,
+}
+
+/// Disk-backed state for the sequencer, stored as an append-only log.
+///
+/// Writes are buffered in memory until [`PersistentState::commit`] is called,
+/// usually at the end of a block. Each commit appends a single batch to the
+/// log, along with its checksum, and syncs the file: a last batch running
+/// past the end of the log was interrupted by a crash and is discarded when
+/// the log is reopened, so a crash never leaves a partially committed block.
+/// A batch with an invalid checksum, or running past a complete batch, is
+/// reported as corrupted and the log is left untouched. Only the location of
+/// each value is kept in memory, values are read from the log when accessed.
+#[derive(Debug)]
+pub struct PersistentState {
+    file: File,
+    len: u64,
+    index: FxHashMap<Key, Location>,
+    pending: FxHashMap<Key, Vec<u8>>,
+    last_block: Option<(BlockNumber, BlockTimestamp)>,
+}
+
+impl PersistentState {
+    /// Opens the log at `path`, creating it if it doesn't exist. A trailing
+    /// batch which wasn't fully written is discarded.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be opened or if the log is corrupted.
+    pub fn open(path: &Path) -> Result<Self, PersistentStateError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)?;
+
+        let file_len = file.metadata()?.len();
+        if file_len < HEADER_LEN {
+            file.set_len(0)?;
+            file.write_all(MAGIC)?;
+            file.write_all(&[VERSION])?;
+            file.sync_all()?;
+        } else {
+            let mut header = [0u8; HEADER_LEN as usize];
+            file.read_exact(&mut header)?;
+            if header[..4] != *MAGIC {
+                return Err(PersistentStateError::Corrupted("invalid magic".into()));
+            }
+            if header[4] != VERSION {
+                return Err(PersistentStateError::Corrupted(format!(
+                    "unsupported log version {}",
+                    header[4]
+                )));
+            }
+        }
+
+        let mut state = Self {
+            file,
+            len: HEADER_LEN,
+            index: FxHashMap::default(),
+            pending: FxHashMap::default(),
+            last_block: None,
+        };
+        state.replay(file_len.max(HEADER_LEN))?;
+        Ok(state)
+    }
+
+    /// Rebuilds the index from the batches of the log.
+    fn replay(&mut self, file_len: u64) -> Result<(), PersistentStateError> {
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(HEADER_LEN))?;
+
+        let mut offset = HEADER_LEN;
+        while offset + BATCH_HEADER_LEN <= file_len {
+            let mut header = [0u8; BATCH_HEADER_LEN as usize];
+            reader.read_exact(&mut header)?;
+            let mut payload_len = [0u8; 8];
+            payload_len.copy_from_slice(&header[..8]);
+            let payload_len = u64::from_le_bytes(payload_len);
+
+            let payload_offset = offset + BATCH_HEADER_LEN;
+            if payload_len > file_len - payload_offset {
+                // Only the last batch can be torn by a crash. If a complete
+                // batch follows, the length of this one is corrupted.
+                let mut tail = Vec::new();
+                reader.read_to_end(&mut tail)?;
+                if contains_batch(&tail) {
+                    return Err(PersistentStateError::Corrupted(format!(
+                        "invalid length of the batch at offset {}",
+                        offset
+                    )));
+                }
+                break;
+            }
+            let mut payload = Vec::new();
+            Read::take(&mut reader, payload_len).read_to_end(&mut payload)?;
+            if header[8..] != Sha256::digest(&payload)[..] {
+                return Err(PersistentStateError::Corrupted(format!(
+                    "invalid checksum of the batch at offset {}",
+                    offset
+                )));
+            }
+
+            let mut cursor = Cursor::new(payload.as_slice());
+            let block_number = BlockNumber(read_u64(&mut cursor)?);
+            let block_timestamp = BlockTimestamp(read_u64(&mut cursor)?);
+            let n_records = read_u64(&mut cursor)?;
+            for _ in 0..n_records {
+                let key = Key::decode(&mut cursor)?;
+                let len = read_u64(&mut cursor)?;
+                let location = Location {
+                    offset: payload_offset + cursor.position(),
+                    len,
+                };
+                cursor.seek(SeekFrom::Current(len as i64))?;
+                self.index.insert(key, location);
+            }
+            if cursor.position() != payload_len {
+                return Err(PersistentStateError::Corrupted(format!(
+                    "invalid batch at offset {}",
+                    offset
+                )));
+            }
+
+            self.last_block = Some((block_number, block_timestamp));
+            offset = payload_offset + payload_len;
+        }
+
+        // Discard the batch interrupted by a crash, if any, which runs past
+        // the end of the log.
+        if offset < file_len {
+            self.file.set_len(offset)?;
+            self.file.sync_all()?;
+        }
+        self.len = offset;
+        Ok(())
+    }
+
+    /// Appends the pending writes to the log as a single batch, tagged with
+    /// the block number and timestamp, and syncs the file.
+    ///
+    /// # Errors
+    ///
+    /// If the write or the sync of the log fails.
+    pub fn commit(
+        &mut self,
+        block_number: BlockNumber,
+        block_timestamp: BlockTimestamp,
+    ) -> Result<(), PersistentStateError> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&block_number.0.to_le_bytes());
+        payload.extend_from_slice(&block_timestamp.0.to_le_bytes());
+        payload.extend_from_slice(&(self.pending.len() as u64).to_le_bytes());
+
+        let payload_offset = self.len + BATCH_HEADER_LEN;
+        let mut locations = Vec::with_capacity(self.pending.len());
+        for (key, value) in &self.pending {
+            key.encode(&mut payload);
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            locations.push((
+                *key,
+                Location {
+                    offset: payload_offset + payload.len() as u64,
+                    len: value.len() as u64,
+                },
+            ));
+            payload.extend_from_slice(value);
+        }
+
+        let mut batch = Vec::with_capacity(payload.len() + BATCH_HEADER_LEN as usize);
+        batch.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        batch.extend_from_slice(&Sha256::digest(&payload));
+        batch.extend_from_slice(&payload);
+
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.file.write_all(&batch)?;
+        self.file.sync_data()?;
+
+        self.len += batch.len() as u64;
+        self.index.extend(locations);
+        self.pending.clear();
+        self.last_block = Some((block_number, block_timestamp));
+        Ok(())
+    }
+
+    /// Commits the pending writes at the block of the provided context.
+    ///
+    /// # Errors
+    ///
+    /// If the write or the sync of the log fails.
+    pub fn commit_block(
+        &mut self,
+        block_context: &BlockContext,
+    ) -> Result<(), PersistentStateError> {
+        self.commit(block_context.block_number, block_context.block_timestamp)
+    }
+
+    /// Discards the writes made since the last commit.
+    pub fn rollback(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Returns true if writes were made since the last commit.
+    pub fn has_pending_writes(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Returns the block number and timestamp of the last commit, if any.
+    pub const fn last_block(&self) -> Option<(BlockNumber, BlockTimestamp)> {
+        self.last_block
+    }
+
+    /// Returns the size of the log, in bytes.
+    pub const fn log_size(&self) -> u64 {
+        self.len
+    }
+
+    fn get(&mut self, key: &Key) -> Result<Option<Vec<u8>>, PersistentStateError> {
+        if let Some(value) = self.pending.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        let Some(location) = self.index.get(key).copied() else {
+            return Ok(None);
+        };
+
+        let mut value = vec![0u8; location.len as usize];
+        self.file.seek(SeekFrom::Start(location.offset))?;
+        self.file.read_exact(&mut value)?;
+        Ok(Some(value))
+    }
+
+    fn get_felt(&mut self, key: &Key) -> StateResult<Option<StarkFelt>> {
+        let Some(value) = self.get(key).map_err(to_state_error)? else {
+            return Ok(None);
+        };
+        let bytes: [u8; 32] = value
+            .try_into()
+            .map_err(|_| StateError::StateReadError(format!("invalid felt for {:?}", key)))?;
+        Ok(Some(StarkFelt::new(bytes)?))
+    }
+
+    fn set_felt(&mut self, key: Key, felt: &StarkFelt) {
+        self.pending.insert(key, felt.bytes().to_vec());
+    }
+}
+
+fn to_state_error(error: PersistentStateError) -> StateError {
+    StateError::StateReadError(error.to_string())
+}
+
+/// Returns true if a complete batch with a valid checksum starts anywhere in
+/// `bytes`.
+fn contains_batch(bytes: &[u8]) -> bool {
+    let header_len = BATCH_HEADER_LEN as usize;
+    (0..bytes.len().saturating_sub(header_len - 1)).any(|start| {
+        let header = &bytes[start..start + header_len];
+        let mut payload_len = [0u8; 8];
+        payload_len.copy_from_slice(&header[..8]);
+        let payload_len = u64::from_le_bytes(payload_len);
+
+        let payload_start = start + header_len;
+        payload_len <= (bytes.len() - payload_start) as u64
+            && header[8..]
+                == Sha256::digest(&bytes[payload_start..payload_start + payload_len as usize])[..]
+    })
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, PersistentStateError> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_felt(reader: &mut impl Read) -> Result<StarkFelt, PersistentStateError> {
+    let mut buf = [0u8; 32];
+    reader.read_exact(&mut buf)?;
+    Ok(StarkFelt::new(buf)?)
+}
+
+fn read_address(reader: &mut impl Read) -> Result<ContractAddress, PersistentStateError> {
+    Ok(ContractAddress(PatriciaKey::try_from(read_felt(reader)?)?))
+}
+
+impl Committer<PersistentState> for &mut PersistentState {}
+
+impl BlockifierState for &mut PersistentState {
+    fn set_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+        value: StarkFelt,
+    ) {
+        self.set_felt(Key::Storage((contract_address, key)), &value);
+    }
+
+    /// # Errors
+    ///
+    /// If the nonce overflows or fails to be read.
+    fn increment_nonce(&mut self, contract_address: ContractAddress) -> StateResult<()> {
+        let current_nonce = self.get_nonce_at(contract_address)?;
+
+        let mut current_nonce: u64 = current_nonce.0.try_into()?;
+        if current_nonce == u64::MAX {
+            return Err(StateError::StateReadError("Nonce overflow".into()));
+        }
+        current_nonce += 1;
+
+        self.set_felt(
+            Key::Nonce(contract_address),
+            &StarkFelt::from(current_nonce),
+        );
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// If the contract address is linked to a class hash.
+    fn set_class_hash_at(
+        &mut self,
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+    ) -> StateResult<()> {
+        if self.get_felt(&Key::Contract(contract_address))?.is_some() {
+            return Err(StateError::UnavailableContractAddress(contract_address));
+        }
+        self.set_felt(Key::Contract(contract_address), &class_hash.0);
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// If the contract class fails to serialize.
+    fn set_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+        contract_class: ContractClass,
+    ) -> StateResult<()> {
+        let class = serde_json::to_vec(&contract_class)
+            .map_err(|error| StateError::StateReadError(error.to_string()))?;
+        self.pending.insert(Key::Class(*class_hash), class);
+        Ok(())
+    }
+
+    fn set_compiled_class_hash(
+        &mut self,
+        class_hash: ClassHash,
+        compiled_class_hash: CompiledClassHash,
+    ) -> StateResult<()> {
+        self.set_felt(Key::CompiledClassHash(class_hash), &compiled_class_hash.0);
+        Ok(())
+    }
+
+    fn to_state_diff(&mut self) -> CommitmentStateDiff {
+        unreachable!("to_state_diff should not be called in the sequencer")
+    }
+}
+
+impl BlockifierStateReader for &mut PersistentState {
+    fn get_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        Ok(self
+            .get_felt(&Key::Storage((contract_address, key)))?
+            .unwrap_or_default())
+    }
+
+    fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        Ok(Nonce(
+            self.get_felt(&Key::Nonce(contract_address))?
+                .unwrap_or_default(),
+        ))
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        Ok(ClassHash(
+            self.get_felt(&Key::Contract(contract_address))?
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class is not declared or fails to deserialize.
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> StateResult<ContractClass> {
+        let class = self
+            .get(&Key::Class(*class_hash))
+            .map_err(to_state_error)?
+            .ok_or(StateError::UndeclaredClassHash(*class_hash))?;
+        serde_json::from_slice(&class)
+            .map_err(|error| StateError::StateReadError(error.to_string()))
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class hash is not declared.
+    fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        self.get_felt(&Key::CompiledClassHash(class_hash))?
+            .map(CompiledClassHash)
+            .ok_or(StateError::UndeclaredClassHash(class_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::execution::contract_class::ContractClassV0;
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_CONTRACT, TWO_FELT,
+    };
+
+    use super::*;
+
+    fn counter_class() -> ContractClass {
+        let contract_class = include_str!("./test_data/cairo_0/compiled_classes/counter.json");
+        ContractClass::V0(serde_json::from_str::<ContractClassV0>(contract_class).unwrap())
+    }
+
+    #[test]
+    fn test_persistent_state_survives_reopen() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let mut state = PersistentState::open(&path).unwrap();
+
+        (&mut state)
+            .set_contract_class(&ONE_CLASS_HASH, counter_class())
+            .unwrap();
+        (&mut state)
+            .set_compiled_class_hash(*ONE_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        (&mut state).increment_nonce(*TEST_CONTRACT).unwrap();
+
+        // When
+        state.commit(BlockNumber(1), BlockTimestamp(10)).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        state.commit(BlockNumber(2), BlockTimestamp(20)).unwrap();
+        drop(state);
+        let mut state = PersistentState::open(&path).unwrap();
+
+        // Then
+        assert_eq!(
+            state.last_block(),
+            Some((BlockNumber(2), BlockTimestamp(20)))
+        );
+        let mut state = &mut state;
+        assert_eq!(
+            state
+                .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+                .unwrap(),
+            *TWO_FELT
+        );
+        assert_eq!(
+            state.get_nonce_at(*TEST_CONTRACT).unwrap(),
+            Nonce(*ONE_FELT)
+        );
+        assert_eq!(
+            state.get_class_hash_at(*TEST_CONTRACT).unwrap(),
+            *ONE_CLASS_HASH
+        );
+        assert_eq!(
+            state.get_compiled_class_hash(*ONE_CLASS_HASH).unwrap(),
+            *ONE_COMPILED_CLASS_HASH
+        );
+        assert_eq!(
+            state.get_compiled_contract_class(&ONE_CLASS_HASH).unwrap(),
+            counter_class()
+        );
+    }
+
+    #[test]
+    fn test_persistent_state_uncommitted_writes_are_lost() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let mut state = PersistentState::open(&path).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+
+        // When
+        drop(state);
+        let mut state = PersistentState::open(&path).unwrap();
+
+        // Then
+        assert_eq!(state.last_block(), None);
+        assert_eq!(
+            (&mut state)
+                .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+                .unwrap(),
+            StarkFelt::default()
+        );
+    }
+
+    #[test]
+    fn test_persistent_state_discards_torn_batch() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let mut state = PersistentState::open(&path).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.commit(BlockNumber(1), BlockTimestamp(10)).unwrap();
+        let committed_size = state.log_size();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        state.commit(BlockNumber(2), BlockTimestamp(20)).unwrap();
+        drop(state);
+
+        // When
+        // Simulate a crash in the middle of the second commit.
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(committed_size + BATCH_HEADER_LEN + 4).unwrap();
+        drop(file);
+        let mut state = PersistentState::open(&path).unwrap();
+
+        // Then
+        assert_eq!(state.log_size(), committed_size);
+        assert_eq!(
+            state.last_block(),
+            Some((BlockNumber(1), BlockTimestamp(10)))
+        );
+        assert_eq!(
+            (&mut state)
+                .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+                .unwrap(),
+            *ONE_FELT
+        );
+    }
+
+    #[test]
+    fn test_persistent_state_rejects_corrupted_batch() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let mut state = PersistentState::open(&path).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.commit(BlockNumber(1), BlockTimestamp(10)).unwrap();
+        let committed_size = state.log_size();
+        drop(state);
+
+        // When
+        // Flip the last byte of the payload of the complete batch.
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::Start(committed_size - 1)).unwrap();
+        file.read_exact(&mut last).unwrap();
+        file.seek(SeekFrom::Start(committed_size - 1)).unwrap();
+        file.write_all(&[!last[0]]).unwrap();
+        drop(file);
+        let result = PersistentState::open(&path);
+
+        // Then
+        assert!(matches!(result, Err(PersistentStateError::Corrupted(_))));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), committed_size);
+    }
+
+    #[test]
+    fn test_persistent_state_rejects_corrupted_batch_length() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let mut state = PersistentState::open(&path).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.commit(BlockNumber(1), BlockTimestamp(10)).unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        state.commit(BlockNumber(2), BlockTimestamp(20)).unwrap();
+        let log_size = state.log_size();
+        drop(state);
+
+        // When
+        // Make the length of the first batch run past the end of the log.
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(HEADER_LEN)).unwrap();
+        file.write_all(&log_size.to_le_bytes()).unwrap();
+        drop(file);
+        let result = PersistentState::open(&path);
+
+        // Then
+        assert!(matches!(result, Err(PersistentStateError::Corrupted(_))));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), log_size);
+    }
+}
@@ -0,0 +1,516 @@
+use blockifier::{
+    execution::{
+        call_info::{CallInfo, OrderedEvent as BlockifierOrderedEvent},
+        entry_point::CallType as BlockifierCallType,
+    },
+    transaction::{
+        account_transaction::AccountTransaction, objects::TransactionExecutionInfo,
+        transaction_execution::Transaction,
+    },
+};
+use starknet::core::types::{
+    CallType, DeclareTransactionReceipt, DeclareTransactionTrace, DeployAccountTransactionReceipt,
+    DeployAccountTransactionTrace, EntryPointType, Event, ExecuteInvocation, ExecutionResources,
+    ExecutionResult, FieldElement, FunctionInvocation, InvokeTransactionReceipt,
+    InvokeTransactionTrace, L1HandlerTransactionReceipt, L1HandlerTransactionTrace, MsgToL1,
+    OrderedEvent, OrderedMessage, RevertedInvocation, TransactionFinalityStatus,
+    TransactionReceipt, TransactionTrace,
+};
+use starknet_api::{
+    block::{BlockHash, BlockNumber},
+    core::ContractAddress,
+    deprecated_contract_class::EntryPointType as ApiEntryPointType,
+    hash::StarkFelt,
+    transaction::TransactionHash,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ReceiptConversionError {
+    #[error("missing {0} call info for a successful transaction")]
+    MissingCallInfo(&'static str),
+}
+
+/// Type of an executed transaction, along with the data needed by its
+/// receipt and trace which isn't part of the execution info.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionKind {
+    Invoke,
+    Declare,
+    DeployAccount { contract_address: ContractAddress },
+    L1Handler,
+}
+
+impl From<&Transaction> for TransactionKind {
+    fn from(transaction: &Transaction) -> Self {
+        match transaction {
+            Transaction::AccountTransaction(AccountTransaction::Invoke(_)) => Self::Invoke,
+            Transaction::AccountTransaction(AccountTransaction::Declare(_)) => Self::Declare,
+            Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => {
+                Self::DeployAccount {
+                    contract_address: tx.contract_address,
+                }
+            }
+            Transaction::L1HandlerTransaction(_) => Self::L1Handler,
+        }
+    }
+}
+
+/// Returns the hash of the transaction.
+pub fn transaction_hash(transaction: &Transaction) -> TransactionHash {
+    match transaction {
+        Transaction::AccountTransaction(AccountTransaction::Invoke(tx)) => tx.tx_hash,
+        Transaction::AccountTransaction(AccountTransaction::Declare(tx)) => tx.tx_hash(),
+        Transaction::AccountTransaction(AccountTransaction::DeployAccount(tx)) => tx.tx_hash,
+        Transaction::L1HandlerTransaction(tx) => tx.tx_hash,
+    }
+}
+
+/// Converts the execution info of a transaction to its RPC receipt. The
+/// transaction is considered accepted on L2 in the provided block.
+pub fn to_transaction_receipt(
+    transaction_hash: TransactionHash,
+    kind: TransactionKind,
+    info: &TransactionExecutionInfo,
+    block_hash: BlockHash,
+    block_number: BlockNumber,
+) -> TransactionReceipt {
+    let transaction_hash = to_field_element(&transaction_hash.0);
+    let actual_fee = FieldElement::from(info.actual_fee.0);
+    let finality_status = TransactionFinalityStatus::AcceptedOnL2;
+    let block_hash = to_field_element(&block_hash.0);
+    let block_number = block_number.0;
+    let messages_sent = to_messages_sent(info);
+    let events = to_events(info);
+    let execution_resources = to_execution_resources(info);
+    let execution_result = to_execution_result(info);
+
+    match kind {
+        TransactionKind::Invoke => TransactionReceipt::Invoke(InvokeTransactionReceipt {
+            transaction_hash,
+            actual_fee,
+            finality_status,
+            block_hash,
+            block_number,
+            messages_sent,
+            events,
+            execution_resources,
+            execution_result,
+        }),
+        TransactionKind::Declare => TransactionReceipt::Declare(DeclareTransactionReceipt {
+            transaction_hash,
+            actual_fee,
+            finality_status,
+            block_hash,
+            block_number,
+            messages_sent,
+            events,
+            execution_resources,
+            execution_result,
+        }),
+        TransactionKind::DeployAccount { contract_address } => {
+            TransactionReceipt::DeployAccount(DeployAccountTransactionReceipt {
+                transaction_hash,
+                actual_fee,
+                finality_status,
+                block_hash,
+                block_number,
+                messages_sent,
+                events,
+                execution_resources,
+                execution_result,
+                contract_address: to_field_element(contract_address.0.key()),
+            })
+        }
+        TransactionKind::L1Handler => TransactionReceipt::L1Handler(L1HandlerTransactionReceipt {
+            transaction_hash,
+            actual_fee,
+            finality_status,
+            block_hash,
+            block_number,
+            messages_sent,
+            events,
+            execution_resources,
+            execution_result,
+        }),
+    }
+}
+
+/// Converts the execution info of a transaction to its RPC trace. The state
+/// diff of the trace is left empty.
+///
+/// # Errors
+///
+/// If the call info of a mandatory invocation is missing, e.g. the constructor
+/// invocation of a successful deploy account transaction.
+pub fn to_transaction_trace(
+    kind: TransactionKind,
+    info: &TransactionExecutionInfo,
+) -> Result<TransactionTrace, ReceiptConversionError> {
+    let validate_invocation = info.validate_call_info.as_ref().map(to_function_invocation);
+    let fee_transfer_invocation = info
+        .fee_transfer_call_info
+        .as_ref()
+        .map(to_function_invocation);
+
+    Ok(match kind {
+        TransactionKind::Invoke => {
+            let execute_invocation = match (&info.revert_error, &info.execute_call_info) {
+                (Some(revert_reason), _) => ExecuteInvocation::Reverted(RevertedInvocation {
+                    revert_reason: revert_reason.clone(),
+                }),
+                (None, Some(call_info)) => {
+                    ExecuteInvocation::Success(to_function_invocation(call_info))
+                }
+                (None, None) => return Err(ReceiptConversionError::MissingCallInfo("execute")),
+            };
+            TransactionTrace::Invoke(InvokeTransactionTrace {
+                validate_invocation,
+                execute_invocation,
+                fee_transfer_invocation,
+                state_diff: None,
+            })
+        }
+        TransactionKind::Declare => TransactionTrace::Declare(DeclareTransactionTrace {
+            validate_invocation,
+            fee_transfer_invocation,
+            state_diff: None,
+        }),
+        TransactionKind::DeployAccount { .. } => {
+            let constructor_invocation = info
+                .execute_call_info
+                .as_ref()
+                .map(to_function_invocation)
+                .ok_or(ReceiptConversionError::MissingCallInfo("constructor"))?;
+            TransactionTrace::DeployAccount(DeployAccountTransactionTrace {
+                validate_invocation,
+                constructor_invocation,
+                fee_transfer_invocation,
+                state_diff: None,
+            })
+        }
+        TransactionKind::L1Handler => {
+            let function_invocation = info
+                .execute_call_info
+                .as_ref()
+                .map(to_function_invocation)
+                .ok_or(ReceiptConversionError::MissingCallInfo("l1 handler"))?;
+            TransactionTrace::L1Handler(L1HandlerTransactionTrace {
+                function_invocation,
+                state_diff: None,
+            })
+        }
+    })
+}
+
+/// Converts a call info and its inner calls to an RPC function invocation.
+pub fn to_function_invocation(call_info: &CallInfo) -> FunctionInvocation {
+    let call = &call_info.call;
+    let from_address = to_field_element(call.storage_address.0.key());
+
+    FunctionInvocation {
+        contract_address: from_address,
+        entry_point_selector: to_field_element(&call.entry_point_selector.0),
+        calldata: to_field_elements(call.calldata.0.iter()),
+        caller_address: to_field_element(call.caller_address.0.key()),
+        class_hash: call.class_hash.map_or(FieldElement::ZERO, |class_hash| {
+            to_field_element(&class_hash.0)
+        }),
+        entry_point_type: match call.entry_point_type {
+            ApiEntryPointType::Constructor => EntryPointType::Constructor,
+            ApiEntryPointType::External => EntryPointType::External,
+            ApiEntryPointType::L1Handler => EntryPointType::L1Handler,
+        },
+        call_type: match call.call_type {
+            BlockifierCallType::Call => CallType::Call,
+            BlockifierCallType::Delegate => CallType::LibraryCall,
+        },
+        result: to_field_elements(call_info.execution.retdata.0.iter()),
+        calls: call_info
+            .inner_calls
+            .iter()
+            .map(to_function_invocation)
+            .collect(),
+        events: call_info
+            .execution
+            .events
+            .iter()
+            .map(|event| OrderedEvent {
+                order: event.order as u64,
+                keys: to_field_elements(event.event.keys.iter().map(|key| &key.0)),
+                data: to_field_elements(event.event.data.0.iter()),
+            })
+            .collect(),
+        messages: call_info
+            .execution
+            .l2_to_l1_messages
+            .iter()
+            .map(|message| OrderedMessage {
+                order: message.order as u64,
+                from_address,
+                to_address: FieldElement::from_byte_slice_be(
+                    message.message.to_address.0.as_bytes(),
+                )
+                .expect("an Ethereum address fits in a field element"),
+                payload: to_field_elements(message.message.payload.0.iter()),
+            })
+            .collect(),
+    }
+}
+
+/// Converts the resources used by a transaction to RPC execution resources.
+/// Missing builtins count as zero. Memory holes aren't tracked by the
+/// execution info.
+pub fn to_execution_resources(info: &TransactionExecutionInfo) -> ExecutionResources {
+    let resource = |name: &str| {
+        info.actual_resources
+            .0
+            .get(name)
+            .copied()
+            .unwrap_or_default() as u64
+    };
+
+    ExecutionResources {
+        steps: resource("n_steps"),
+        memory_holes: None,
+        range_check_builtin_applications: resource("range_check_builtin"),
+        pedersen_builtin_applications: resource("pedersen_builtin"),
+        poseidon_builtin_applications: resource("poseidon_builtin"),
+        ec_op_builtin_applications: resource("ec_op_builtin"),
+        ecdsa_builtin_applications: resource("ecdsa_builtin"),
+        bitwise_builtin_applications: resource("bitwise_builtin"),
+        keccak_builtin_applications: resource("keccak_builtin"),
+    }
+}
+
+/// Converts the outcome of a transaction to an RPC execution result, keeping
+/// the revert reason of reverted transactions.
+pub fn to_execution_result(info: &TransactionExecutionInfo) -> ExecutionResult {
+    match &info.revert_error {
+        Some(reason) => ExecutionResult::Reverted {
+            reason: reason.clone(),
+        },
+        None => ExecutionResult::Succeeded,
+    }
+}
+
+/// Returns the call infos of the transaction in execution order.
+fn call_infos(info: &TransactionExecutionInfo) -> impl Iterator<Item = &CallInfo> {
+    [
+        &info.validate_call_info,
+        &info.execute_call_info,
+        &info.fee_transfer_call_info,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Collects the events emitted by the transaction. Events are ordered by
+/// phase, then by emission order within each phase.
+pub(crate) fn to_events(info: &TransactionExecutionInfo) -> Vec<Event> {
+    call_infos(info)
+        .flat_map(|call_info| {
+            let mut events: Vec<(&ContractAddress, &BlockifierOrderedEvent)> = call_info
+                .into_iter()
+                .flat_map(|call| {
+                    call.execution
+                        .events
+                        .iter()
+                        .map(move |event| (&call.call.storage_address, event))
+                })
+                .collect();
+            events.sort_by_key(|(_, event)| event.order);
+            events
+        })
+        .map(|(from_address, event)| Event {
+            from_address: to_field_element(from_address.0.key()),
+            keys: to_field_elements(event.event.keys.iter().map(|key| &key.0)),
+            data: to_field_elements(event.event.data.0.iter()),
+        })
+        .collect()
+}
+
+/// Collects the messages sent to L1 by the transaction, ordered like the
+/// events.
+fn to_messages_sent(info: &TransactionExecutionInfo) -> Vec<MsgToL1> {
+    call_infos(info)
+        .flat_map(|call_info| {
+            let mut messages: Vec<_> = call_info
+                .into_iter()
+                .flat_map(|call| {
+                    call.execution
+                        .l2_to_l1_messages
+                        .iter()
+                        .map(move |message| (&call.call.storage_address, message))
+                })
+                .collect();
+            messages.sort_by_key(|(_, message)| message.order);
+            messages
+        })
+        .map(|(from_address, message)| MsgToL1 {
+            from_address: to_field_element(from_address.0.key()),
+            to_address: FieldElement::from_byte_slice_be(message.message.to_address.0.as_bytes())
+                .expect("an Ethereum address fits in a field element"),
+            payload: to_field_elements(message.message.payload.0.iter()),
+        })
+        .collect()
+}
+
+fn to_field_element(felt: &StarkFelt) -> FieldElement {
+    FieldElement::from_bytes_be(felt.bytes()).expect("a StarkFelt is a valid field element")
+}
+
+fn to_field_elements<'a>(felts: impl Iterator<Item = &'a StarkFelt>) -> Vec<FieldElement> {
+    felts.map(to_field_element).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{
+        execution::{
+            call_info::{CallExecution, Retdata},
+            entry_point::CallEntryPoint,
+        },
+        transaction::objects::ResourcesMapping,
+    };
+    use starknet_api::{
+        core::{ClassHash, EntryPointSelector},
+        transaction::{EventContent, EventData, EventKey, Fee},
+    };
+
+    use crate::constants::test_constants::{
+        ONE_BLOCK_NUMBER, ONE_FELT, TEST_ACCOUNT, TEST_CONTRACT, TWO_FELT,
+    };
+
+    use super::*;
+
+    fn call_info(
+        storage_address: ContractAddress,
+        order: usize,
+        inner_calls: Vec<CallInfo>,
+    ) -> CallInfo {
+        CallInfo {
+            call: CallEntryPoint {
+                class_hash: Some(ClassHash(*ONE_FELT)),
+                storage_address,
+                entry_point_selector: EntryPointSelector(*TWO_FELT),
+                ..Default::default()
+            },
+            execution: CallExecution {
+                retdata: Retdata(vec![*ONE_FELT]),
+                events: vec![BlockifierOrderedEvent {
+                    order,
+                    event: EventContent {
+                        keys: vec![EventKey(*ONE_FELT)],
+                        data: EventData(vec![*TWO_FELT]),
+                    },
+                }],
+                ..Default::default()
+            },
+            inner_calls,
+            ..Default::default()
+        }
+    }
+
+    fn execution_info(revert_error: Option<String>) -> TransactionExecutionInfo {
+        let inner = call_info(*TEST_CONTRACT, 0, vec![]);
+        TransactionExecutionInfo {
+            validate_call_info: None,
+            execute_call_info: revert_error
+                .is_none()
+                .then(|| call_info(*TEST_ACCOUNT, 1, vec![inner])),
+            fee_transfer_call_info: None,
+            actual_fee: Fee(10),
+            actual_resources: ResourcesMapping(
+                [
+                    (String::from("n_steps"), 100),
+                    (String::from("range_check_builtin"), 3),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            revert_error,
+        }
+    }
+
+    #[test]
+    fn test_to_transaction_receipt() {
+        // Given
+        let info = execution_info(None);
+
+        // When
+        let receipt = to_transaction_receipt(
+            TransactionHash(*ONE_FELT),
+            TransactionKind::Invoke,
+            &info,
+            BlockHash(*TWO_FELT),
+            *ONE_BLOCK_NUMBER,
+        );
+
+        // Then
+        let TransactionReceipt::Invoke(receipt) = receipt else {
+            panic!("expected an invoke receipt");
+        };
+        assert_eq!(receipt.actual_fee, FieldElement::from(10u8));
+        assert_eq!(receipt.block_number, ONE_BLOCK_NUMBER.0);
+        assert_eq!(receipt.execution_result, ExecutionResult::Succeeded);
+        assert_eq!(receipt.execution_resources.steps, 100);
+        assert_eq!(
+            receipt.execution_resources.range_check_builtin_applications,
+            3
+        );
+        assert_eq!(receipt.execution_resources.pedersen_builtin_applications, 0);
+        // The event of the inner call is emitted first.
+        let from_addresses: Vec<_> = receipt.events.iter().map(|e| e.from_address).collect();
+        assert_eq!(
+            from_addresses,
+            vec![
+                to_field_element(TEST_CONTRACT.0.key()),
+                to_field_element(TEST_ACCOUNT.0.key())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_transaction_trace_reverted() {
+        // Given
+        let info = execution_info(Some(String::from("out of gas")));
+
+        // When
+        let trace = to_transaction_trace(TransactionKind::Invoke, &info).unwrap();
+        let result = to_execution_result(&info);
+
+        // Then
+        let TransactionTrace::Invoke(trace) = trace else {
+            panic!("expected an invoke trace");
+        };
+        assert!(matches!(
+            trace.execute_invocation,
+            ExecuteInvocation::Reverted(RevertedInvocation { revert_reason }) if revert_reason == "out of gas"
+        ));
+        assert_eq!(
+            result,
+            ExecutionResult::Reverted {
+                reason: String::from("out of gas")
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_transaction_trace_missing_constructor() {
+        // Given
+        let info = execution_info(Some(String::from("reverted")));
+        let kind = TransactionKind::DeployAccount {
+            contract_address: *TEST_ACCOUNT,
+        };
+
+        // When
+        let trace = to_transaction_trace(kind, &info);
+
+        // Then
+        assert_eq!(
+            trace.unwrap_err(),
+            ReceiptConversionError::MissingCallInfo("constructor")
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! Declarative scenarios for sequencer tests, written in YAML or JSON.
+//!
+//! A scenario holds a block context and a list of steps, run in order on a
+//! fresh sequencer. Felts are written as hex strings (`"0x1f"`), decimal
+//! strings or numbers, or as `"selector:<name>"` for entry point selectors.
+//!
+//! ```yaml
+//! block_context: { ... }
+//! steps:
+//!   - declare: { class_hash: "0x1", path: "counter.json" }
+//!   - deploy: { address: "0x1", class_hash: "0x1" }
+//!   - invoke:
+//!       sender: "0x2"
+//!       calldata: ["0x1", "selector:inc", 0]
+//!   - expect_storage: { address: "0x1", variable: "counter", value: 1 }
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use blockifier::{
+    abi::abi_utils::get_storage_var_address,
+    block_context::BlockContext,
+    state::{
+        errors::StateError,
+        state_api::{State as BlockifierState, StateReader as BlockifierStateReader},
+    },
+    transaction::{
+        account_transaction::AccountTransaction,
+        objects::{TransactionExecutionInfo, TransactionExecutionResult},
+        transaction_execution::Transaction,
+        transactions::InvokeTransaction,
+    },
+};
+use serde::Deserialize;
+use starknet::core::{types::FieldElement, utils::get_selector_from_name};
+use starknet_api::{
+    core::{ClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    state::StorageKey,
+    transaction::{
+        Calldata, Fee, InvokeTransaction as ApiInvokeTransaction, InvokeTransactionV1,
+        TransactionHash, TransactionSignature,
+    },
+    StarknetApiError,
+};
+use thiserror::Error;
+
+use crate::{
+    block_context::SerializableBlockContext,
+    class_registry::{load_class_file, ClassRegistryError},
+    execution::Execution,
+    receipt::to_events,
+    sequencer::Sequencer,
+    state::State,
+    transaction::compute_transaction_hash,
+};
+
+#[derive(Error, Debug)]
+pub enum ScenarioError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    SerdeYamlError(#[from] serde_yaml::Error),
+    #[error("step {step}: {source}")]
+    ClassRegistryError {
+        step: usize,
+        source: ClassRegistryError,
+    },
+    #[error("step {step}: {source}")]
+    StateError { step: usize, source: StateError },
+    #[error("step {step}: {source}")]
+    StarknetApiError {
+        step: usize,
+        source: StarknetApiError,
+    },
+    #[error("step {step}: expectation failed: {reason}")]
+    ExpectationFailed { step: usize, reason: String },
+}
+
+/// Felt written as a hex string, a decimal string or number, or a selector
+/// name prefixed by `selector:`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawFelt")]
+pub struct Felt(pub StarkFelt);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFelt {
+    Number(u64),
+    String(String),
+}
+
+impl TryFrom<RawFelt> for Felt {
+    type Error = String;
+
+    fn try_from(raw: RawFelt) -> Result<Self, Self::Error> {
+        let felt = match raw {
+            RawFelt::Number(value) => FieldElement::from(value),
+            RawFelt::String(value) => {
+                if let Some(name) = value.strip_prefix("selector:") {
+                    get_selector_from_name(name).map_err(|err| err.to_string())?
+                } else if value.starts_with("0x") {
+                    FieldElement::from_hex_be(&value).map_err(|err| err.to_string())?
+                } else {
+                    FieldElement::from_dec_str(&value).map_err(|err| err.to_string())?
+                }
+            }
+        };
+        Ok(Self(felt.into()))
+    }
+}
+
+impl Felt {
+    fn to_field_element(self) -> FieldElement {
+        FieldElement::from_bytes_be(self.0.bytes()).expect("a StarkFelt is a valid field element")
+    }
+}
+
+/// Storage slot, either addressed by its key or by the name of the storage
+/// variable and its arguments.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct StorageSlot {
+    pub address: Felt,
+    pub key: Option<Felt>,
+    pub variable: Option<String>,
+    #[serde(default)]
+    pub args: Vec<Felt>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedStatus {
+    Succeeded,
+    Reverted,
+    Rejected,
+}
+
+/// Expected event. Omitted fields match any value.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ExpectedEvent {
+    pub from_address: Option<Felt>,
+    pub keys: Option<Vec<Felt>>,
+    pub data: Option<Vec<Felt>>,
+}
+
+/// Expected outcome of an invoke. The status defaults to succeeded, unless
+/// a revert reason is expected, in which case the transaction must either
+/// revert or be rejected with an error containing the reason.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Expectation {
+    pub status: Option<ExpectedStatus>,
+    pub revert_reason: Option<String>,
+    #[serde(default)]
+    pub events: Vec<ExpectedEvent>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct InvokeStep {
+    pub sender: Felt,
+    pub calldata: Vec<Felt>,
+    #[serde(default)]
+    pub signature: Vec<Felt>,
+    /// Defaults to the current nonce of the sender.
+    pub nonce: Option<Felt>,
+    #[serde(default)]
+    pub max_fee: u128,
+    /// Skips the validation of the transaction by the sender.
+    #[serde(default)]
+    pub impersonate: bool,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+/// Step of a scenario.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Declares the class from a class file, whose path is relative to the
+    /// scenario file. Cairo 0, CASM and Sierra classes are supported.
+    Declare {
+        class_hash: Felt,
+        path: PathBuf,
+    },
+    /// Deploys the class at the address, without calling the constructor.
+    Deploy {
+        address: Felt,
+        class_hash: Felt,
+    },
+    SetStorage {
+        #[serde(flatten)]
+        slot: StorageSlot,
+        value: Felt,
+    },
+    /// Sets the balance of the address on the ETH fee token.
+    SetBalance {
+        address: Felt,
+        amount: u128,
+    },
+    Invoke(Box<InvokeStep>),
+    ExpectStorage {
+        #[serde(flatten)]
+        slot: StorageSlot,
+        value: Felt,
+    },
+    ExpectNonce {
+        address: Felt,
+        nonce: Felt,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub block_context: SerializableBlockContext,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Reads the scenario file, as JSON if its extension is `.json`, as YAML
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// If the file can't be read or parsed.
+    pub fn from_file(path: &Path) -> Result<Self, ScenarioError> {
+        let content = fs::read_to_string(path)?;
+        if path
+            .extension()
+            .map_or(false, |extension| extension == "json")
+        {
+            Ok(serde_json::from_str(&content)?)
+        } else {
+            Ok(serde_yaml::from_str(&content)?)
+        }
+    }
+
+    /// Runs the steps of the scenario on a fresh sequencer and returns the
+    /// sequencer. Class paths are resolved relative to `base_dir`.
+    ///
+    /// # Errors
+    ///
+    /// If a step fails or an expectation isn't met. The error holds the index
+    /// of the failing step.
+    pub fn run(&self, base_dir: &Path) -> Result<Sequencer<State, ()>, ScenarioError> {
+        let block_context = BlockContext::from(self.block_context.clone());
+        let chain_id = FieldElement::from_byte_slice_be(block_context.chain_id.0.as_bytes())
+            .expect("the chain id fits in a field element");
+        let mut sequencer = Sequencer::new(block_context, State::default(), ());
+
+        for (step, action) in self.steps.iter().enumerate() {
+            run_step(&mut sequencer, chain_id, base_dir, action).map_err(|err| err.at(step))?;
+        }
+
+        Ok(sequencer)
+    }
+}
+
+/// Reads and runs the scenario file.
+///
+/// # Errors
+///
+/// If the file can't be read or if the scenario fails.
+pub fn run_scenario_file(path: &Path) -> Result<Sequencer<State, ()>, ScenarioError> {
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    Scenario::from_file(path)?.run(base_dir)
+}
+
+/// Error of a step, before it's attached to the index of the step.
+enum StepError {
+    ClassRegistry(ClassRegistryError),
+    State(StateError),
+    StarknetApi(StarknetApiError),
+    Expectation(String),
+}
+
+impl StepError {
+    fn at(self, step: usize) -> ScenarioError {
+        match self {
+            Self::ClassRegistry(source) => ScenarioError::ClassRegistryError { step, source },
+            Self::State(source) => ScenarioError::StateError { step, source },
+            Self::StarknetApi(source) => ScenarioError::StarknetApiError { step, source },
+            Self::Expectation(reason) => ScenarioError::ExpectationFailed { step, reason },
+        }
+    }
+}
+
+impl From<ClassRegistryError> for StepError {
+    fn from(err: ClassRegistryError) -> Self {
+        Self::ClassRegistry(err)
+    }
+}
+
+impl From<StateError> for StepError {
+    fn from(err: StateError) -> Self {
+        Self::State(err)
+    }
+}
+
+impl From<StarknetApiError> for StepError {
+    fn from(err: StarknetApiError) -> Self {
+        Self::StarknetApi(err)
+    }
+}
+
+fn run_step(
+    sequencer: &mut Sequencer<State, ()>,
+    chain_id: FieldElement,
+    base_dir: &Path,
+    step: &Step,
+) -> Result<(), StepError> {
+    match step {
+        Step::Declare { class_hash, path } => {
+            let class_hash = ClassHash(class_hash.0);
+            let (contract_class, compiled_class_hash) = load_class_file(&base_dir.join(path))?;
+            (&mut sequencer.state).set_contract_class(&class_hash, contract_class)?;
+            if let Some(compiled_class_hash) = compiled_class_hash {
+                (&mut sequencer.state).set_compiled_class_hash(class_hash, compiled_class_hash)?;
+            }
+        }
+        Step::Deploy {
+            address,
+            class_hash,
+        } => {
+            (&mut sequencer.state)
+                .set_class_hash_at(to_address(*address)?, ClassHash(class_hash.0))?;
+        }
+        Step::SetStorage { slot, value } => {
+            let (address, key) = to_storage_slot(slot)?;
+            (&mut sequencer.state).set_storage_at(address, key, value.0);
+        }
+        Step::SetBalance { address, amount } => {
+            sequencer.set_fee_token_balance(to_address(*address)?, [*amount, 0])?;
+        }
+        Step::Invoke(invoke) => {
+            let sender = to_address(invoke.sender)?;
+            let transaction = invoke_transaction(sequencer, chain_id, sender, invoke)?;
+
+            if invoke.impersonate {
+                sequencer.impersonate_account(sender);
+            }
+            let result = sequencer.execute(transaction);
+            if invoke.impersonate {
+                sequencer.stop_impersonating_account(sender);
+            }
+
+            check_outcome(&invoke.expect, &result).map_err(StepError::Expectation)?;
+        }
+        Step::ExpectStorage { slot, value } => {
+            let (address, key) = to_storage_slot(slot)?;
+            let actual = (&mut sequencer.state).get_storage_at(address, key)?;
+            if actual != value.0 {
+                return Err(StepError::Expectation(format!(
+                    "storage of {} at {} is {}, expected {}",
+                    address.0.key(),
+                    key.0.key(),
+                    actual,
+                    value.0
+                )));
+            }
+        }
+        Step::ExpectNonce { address, nonce } => {
+            let address = to_address(*address)?;
+            let actual = (&mut sequencer.state).get_nonce_at(address)?;
+            if actual != Nonce(nonce.0) {
+                return Err(StepError::Expectation(format!(
+                    "nonce of {} is {}, expected {}",
+                    address.0.key(),
+                    actual.0,
+                    nonce.0
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invoke_transaction(
+    sequencer: &mut Sequencer<State, ()>,
+    chain_id: FieldElement,
+    sender: ContractAddress,
+    invoke: &InvokeStep,
+) -> Result<Transaction, StepError> {
+    let nonce = match invoke.nonce {
+        Some(nonce) => Nonce(nonce.0),
+        None => (&mut sequencer.state).get_nonce_at(sender)?,
+    };
+    let calldata: Vec<FieldElement> = invoke
+        .calldata
+        .iter()
+        .map(|felt| felt.to_field_element())
+        .collect();
+    let tx_hash = compute_transaction_hash(
+        Felt(*sender.0.key()).to_field_element(),
+        &calldata,
+        FieldElement::from(invoke.max_fee),
+        chain_id,
+        Felt(nonce.0).to_field_element(),
+    );
+
+    Ok(Transaction::AccountTransaction(AccountTransaction::Invoke(
+        InvokeTransaction {
+            tx: ApiInvokeTransaction::V1(InvokeTransactionV1 {
+                max_fee: Fee(invoke.max_fee),
+                signature: TransactionSignature(
+                    invoke.signature.iter().map(|felt| felt.0).collect(),
+                ),
+                nonce,
+                sender_address: sender,
+                calldata: Calldata(Arc::new(
+                    invoke.calldata.iter().map(|felt| felt.0).collect(),
+                )),
+            }),
+            tx_hash: TransactionHash(tx_hash.into()),
+            only_query: false,
+        },
+    )))
+}
+
+fn check_outcome(
+    expectation: &Expectation,
+    result: &TransactionExecutionResult<TransactionExecutionInfo>,
+) -> Result<(), String> {
+    let (status, error) = match result {
+        Ok(info) => match &info.revert_error {
+            Some(revert_error) => (ExpectedStatus::Reverted, Some(revert_error.clone())),
+            None => (ExpectedStatus::Succeeded, None),
+        },
+        Err(err) => (ExpectedStatus::Rejected, Some(err.to_string())),
+    };
+
+    match (expectation.status, &expectation.revert_reason) {
+        (Some(expected), _) if expected != status => {
+            return Err(format!(
+                "transaction is {:?}, expected {:?}: {}",
+                status,
+                expected,
+                error.unwrap_or_default()
+            ));
+        }
+        (None, None) if status != ExpectedStatus::Succeeded => {
+            return Err(format!(
+                "transaction is {:?}: {}",
+                status,
+                error.unwrap_or_default()
+            ));
+        }
+        _ => {}
+    }
+
+    if let Some(revert_reason) = &expectation.revert_reason {
+        match &error {
+            Some(error) if error.contains(revert_reason.as_str()) => {}
+            Some(error) => {
+                return Err(format!(
+                    "revert reason {:?} doesn't contain {:?}",
+                    error, revert_reason
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "transaction succeeded, expected revert reason {:?}",
+                    revert_reason
+                ))
+            }
+        }
+    }
+
+    if expectation.events.is_empty() {
+        return Ok(());
+    }
+    let Ok(info) = result else {
+        return Err(String::from("rejected transaction emitted no event"));
+    };
+    let events = to_events(info);
+    let matches = |expected: &Option<Vec<Felt>>, actual: &[FieldElement]| {
+        expected.as_ref().map_or(true, |expected| {
+            expected
+                .iter()
+                .map(|felt| felt.to_field_element())
+                .eq(actual.iter().copied())
+        })
+    };
+    for expected in &expectation.events {
+        let found = events.iter().any(|event| {
+            expected.from_address.map_or(true, |address| {
+                address.to_field_element() == event.from_address
+            }) && matches(&expected.keys, &event.keys)
+                && matches(&expected.data, &event.data)
+        });
+        if !found {
+            return Err(format!("missing event {:?}", expected));
+        }
+    }
+    Ok(())
+}
+
+fn to_address(felt: Felt) -> Result<ContractAddress, StarknetApiError> {
+    Ok(ContractAddress(PatriciaKey::try_from(felt.0)?))
+}
+
+fn to_storage_slot(slot: &StorageSlot) -> Result<(ContractAddress, StorageKey), StepError> {
+    let address = to_address(slot.address)?;
+    let key = match (&slot.key, &slot.variable) {
+        (Some(key), None) => StorageKey(PatriciaKey::try_from(key.0)?),
+        (None, Some(variable)) => {
+            let args: Vec<StarkFelt> = slot.args.iter().map(|arg| arg.0).collect();
+            get_storage_var_address(variable, &args)
+        }
+        _ => {
+            return Err(StepError::Expectation(String::from(
+                "a storage slot needs either a key or a variable",
+            )))
+        }
+    };
+    Ok((address, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO_PATH: &str = "src/test_data/scenarios/counter.yaml";
+
+    #[test]
+    fn test_run_scenario_file() {
+        // When
+        let sequencer = run_scenario_file(Path::new(SCENARIO_PATH)).unwrap();
+
+        // Then
+        let counter = sequencer
+            .state
+            .storage_of(
+                &ContractAddress::from(1u8),
+                &get_storage_var_address("counter", &[]),
+            )
+            .copied();
+        assert_eq!(counter, Some(StarkFelt::from(2u8)));
+    }
+
+    #[test]
+    fn test_failed_expectation() {
+        // Given
+        let mut scenario = Scenario::from_file(Path::new(SCENARIO_PATH)).unwrap();
+        let value = Felt(StarkFelt::from(3u8));
+        scenario.steps.push(Step::ExpectStorage {
+            slot: StorageSlot {
+                address: Felt(StarkFelt::from(1u8)),
+                key: None,
+                variable: Some(String::from("counter")),
+                args: vec![],
+            },
+            value,
+        });
+        let steps = scenario.steps.len();
+
+        // When
+        let result = scenario.run(Path::new("src/test_data/scenarios"));
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(ScenarioError::ExpectationFailed { step, .. }) if step == steps - 1
+        ));
+    }
+
+    #[test]
+    fn test_felt_formats() {
+        // When
+        let felts: Vec<Felt> =
+            serde_yaml::from_str(r#"["0x10", "16", 16, "selector:inc"]"#).unwrap();
+
+        // Then
+        assert_eq!(felts[0], felts[1]);
+        assert_eq!(felts[1], felts[2]);
+        assert_eq!(
+            felts[3].to_field_element(),
+            get_selector_from_name("inc").unwrap()
+        );
+    }
+}
@@ -1,9 +1,19 @@
-use crate::{commit::Committer, execution::Execution};
+use crate::{
+    block_context::{BlockContextBuilder, BlockContextError},
+    commit::Committer,
+    execution::Execution,
+    observer::ExecutionObserver,
+    persistent::{PersistentState, PersistentStateError},
+    sierra::{compile_sierra_class, SierraCompilationError},
+    state::{backend::MapBackend, GenericState},
+};
 use blockifier::{
-    block_context::BlockContext,
+    abi::{abi_utils::get_fee_token_var_address, sierra_types::next_storage_key},
+    block_context::{BlockContext, GasPrices},
+    execution::contract_class::ContractClass,
     state::{
         cached_state::{CachedState, GlobalContractCache},
-        state_api::{State, StateReader},
+        state_api::{State, StateReader, StateResult},
     },
     transaction::{
         objects::{TransactionExecutionInfo, TransactionExecutionResult},
@@ -11,7 +21,13 @@ use blockifier::{
         transactions::ExecutableTransaction,
     },
 };
-use starknet_api::core::ContractAddress;
+use cairo_lang_starknet::contract_class::ContractClass as SierraContractClass;
+use starknet_api::{
+    block::{BlockNumber, BlockTimestamp},
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
+    hash::StarkFelt,
+};
+use std::{collections::BTreeSet, sync::Arc};
 
 /// Sequencer is the main struct of the sequencer crate.
 /// Using a trait bound for the state allows for better
@@ -20,12 +36,18 @@ use starknet_api::core::ContractAddress;
 /// must implement State and `StateReader`. The `for` keyword
 /// indicates that the bound must hold for any lifetime 'any.
 /// For more details, check out [rust-lang docs](https://doc.rust-lang.org/nomicon/hrtb.html)
+/// The block context is private and can only be updated through the block
+/// control methods, which validate the new context.
+/// Registered observers are shared between the clones of a sequencer.
 #[derive(Clone)]
 pub struct Sequencer<S, A>
 where
     for<'any> &'any mut S: State + StateReader,
 {
-    pub block_context: BlockContext,
+    block_context: BlockContext,
+    next_block_timestamp: Option<BlockTimestamp>,
+    impersonated_accounts: BTreeSet<ContractAddress>,
+    observers: Vec<Arc<dyn ExecutionObserver>>,
     pub state: S,
     pub address: A,
 }
@@ -40,10 +62,307 @@ where
     pub const fn new(block_context: BlockContext, state: S, address: A) -> Self {
         Self {
             block_context,
+            next_block_timestamp: None,
+            impersonated_accounts: BTreeSet::new(),
+            observers: Vec::new(),
             state,
             address,
         }
     }
+
+    /// Returns the block context of the current block.
+    #[inline]
+    pub const fn block_context(&self) -> &BlockContext {
+        &self.block_context
+    }
+
+    /// Mines an empty block. The block number is incremented and the timestamp
+    /// is set to the value provided through `set_next_block_timestamp`, if any.
+    /// Otherwise the timestamp is kept unchanged.
+    ///
+    /// # Errors
+    ///
+    /// If the block number overflows or the next block timestamp is invalid.
+    pub fn mine_block(&mut self) -> Result<(), BlockContextError> {
+        self.mine_blocks(1)
+    }
+
+    /// Mines `n` empty blocks. Only the first mined block uses the timestamp
+    /// provided through `set_next_block_timestamp`. Observers are notified of
+    /// the closing of each block, with the context of the closed block.
+    ///
+    /// # Errors
+    ///
+    /// If the block number overflows or the next block timestamp is invalid.
+    pub fn mine_blocks(&mut self, n: u64) -> Result<(), BlockContextError> {
+        self.block_context
+            .block_number
+            .0
+            .checked_add(n)
+            .ok_or(BlockContextError::BlockNumberOverflow)?;
+        let block_timestamp = self
+            .next_block_timestamp
+            .unwrap_or(self.block_context.block_timestamp);
+
+        for _ in 0..n {
+            let block_context = BlockContextBuilder::from(self.block_context.clone())
+                .block_number(BlockNumber(self.block_context.block_number.0 + 1))
+                .block_timestamp(block_timestamp)
+                .build()?;
+
+            for observer in &self.observers {
+                observer.on_block_close(&self.block_context);
+            }
+
+            self.block_context = block_context;
+            self.next_block_timestamp = None;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the timestamp of the next mined block.
+    ///
+    /// # Errors
+    ///
+    /// If the timestamp is lower than the current block timestamp.
+    pub fn set_next_block_timestamp(
+        &mut self,
+        timestamp: BlockTimestamp,
+    ) -> Result<(), BlockContextError> {
+        if timestamp < self.block_context.block_timestamp {
+            return Err(BlockContextError::BlockTimestampDecrease {
+                current: self.block_context.block_timestamp.0,
+                new: timestamp.0,
+            });
+        }
+        self.next_block_timestamp = Some(timestamp);
+        Ok(())
+    }
+
+    /// Increases the timestamp of the next mined block by `seconds`,
+    /// relative to the current block timestamp.
+    ///
+    /// # Errors
+    ///
+    /// If the timestamp overflows.
+    pub fn increase_time(&mut self, seconds: u64) -> Result<(), BlockContextError> {
+        let timestamp = self
+            .block_context
+            .block_timestamp
+            .0
+            .checked_add(seconds)
+            .ok_or(BlockContextError::BlockTimestampOverflow)?;
+        self.set_next_block_timestamp(BlockTimestamp(timestamp))
+    }
+
+    /// Sets the timestamp of the current block.
+    ///
+    /// # Errors
+    ///
+    /// If the timestamp is lower than the current block timestamp.
+    pub fn set_block_timestamp(
+        &mut self,
+        timestamp: BlockTimestamp,
+    ) -> Result<(), BlockContextError> {
+        self.update_block_context(|builder| builder.block_timestamp(timestamp))
+    }
+
+    /// Jumps to the provided block number.
+    ///
+    /// # Errors
+    ///
+    /// If the block number is lower than the current block number.
+    pub fn set_block_number(&mut self, block_number: BlockNumber) -> Result<(), BlockContextError> {
+        self.update_block_context(|builder| builder.block_number(block_number))
+    }
+
+    /// Sets the gas prices of the current block.
+    ///
+    /// # Errors
+    ///
+    /// If the resulting block context is invalid.
+    pub fn set_gas_prices(&mut self, gas_prices: GasPrices) -> Result<(), BlockContextError> {
+        self.update_block_context(|builder| builder.gas_prices(gas_prices))
+    }
+
+    /// Updates the block context through a `BlockContextBuilder`. The context
+    /// is only updated if the built context is valid. A next block timestamp
+    /// lower than the new block timestamp is discarded.
+    ///
+    /// # Errors
+    ///
+    /// If the built block context is invalid.
+    pub fn update_block_context(
+        &mut self,
+        f: impl FnOnce(BlockContextBuilder) -> BlockContextBuilder,
+    ) -> Result<(), BlockContextError> {
+        self.block_context = f(BlockContextBuilder::from(self.block_context.clone())).build()?;
+        if self
+            .next_block_timestamp
+            .is_some_and(|timestamp| timestamp < self.block_context.block_timestamp)
+        {
+            self.next_block_timestamp = None;
+        }
+        Ok(())
+    }
+
+    /// Replaces the block context without any validation. Only used to
+    /// replay recorded transactions under their original context.
+    pub(crate) fn set_block_context(&mut self, block_context: BlockContext) {
+        self.block_context = block_context;
+        self.next_block_timestamp = None;
+    }
+
+    /// Registers an observer, called along the lifecycle of transactions and
+    /// blocks. Observers are called in their registration order.
+    pub fn add_observer(&mut self, observer: Arc<dyn ExecutionObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Removes all the registered observers.
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// Impersonates the provided account: transactions sent from this
+    /// address are executed without running the account validation.
+    pub fn impersonate_account(&mut self, address: ContractAddress) {
+        self.impersonated_accounts.insert(address);
+    }
+
+    /// Stops impersonating the provided account.
+    pub fn stop_impersonating_account(&mut self, address: ContractAddress) {
+        self.impersonated_accounts.remove(&address);
+    }
+
+    /// Returns true if the provided account is impersonated.
+    pub fn is_impersonated(&self, address: &ContractAddress) -> bool {
+        self.impersonated_accounts.contains(address)
+    }
+
+    /// Sets the balance of the address on the ETH fee token, following the
+    /// ERC20 storage layout: the balance is an u256 split in [low, high].
+    ///
+    /// # Errors
+    ///
+    /// If the storage key of the high part of the balance overflows.
+    pub fn set_fee_token_balance(
+        &mut self,
+        address: ContractAddress,
+        balance: [u128; 2],
+    ) -> StateResult<()> {
+        let fee_token_address = self.block_context.fee_token_addresses.eth_fee_token_address;
+        let low_key = get_fee_token_var_address(&address);
+        let high_key = next_storage_key(&low_key)?;
+
+        let [low, high] = balance;
+        (&mut self.state).set_storage_at(fee_token_address, low_key, StarkFelt::from(low));
+        (&mut self.state).set_storage_at(fee_token_address, high_key, StarkFelt::from(high));
+
+        Ok(())
+    }
+
+    /// Declares the contract class and deploys it at the provided address.
+    ///
+    /// # Errors
+    ///
+    /// If the address is already linked to a class hash.
+    pub fn deploy_class_at(
+        &mut self,
+        address: ContractAddress,
+        class_hash: ClassHash,
+        contract_class: ContractClass,
+    ) -> StateResult<()> {
+        (&mut self.state).set_contract_class(&class_hash, contract_class)?;
+        (&mut self.state).set_class_hash_at(address, class_hash)?;
+        Ok(())
+    }
+
+    /// Compiles the Sierra class to CASM and declares it, along with its
+    /// compiled class hash.
+    ///
+    /// # Errors
+    ///
+    /// If the compilation fails or if the compiled class hash doesn't match the
+    /// declared one.
+    pub fn declare_sierra_class(
+        &mut self,
+        class_hash: ClassHash,
+        sierra_class: SierraContractClass,
+        compiled_class_hash: CompiledClassHash,
+    ) -> Result<(), SierraCompilationError> {
+        let contract_class = compile_sierra_class(sierra_class, compiled_class_hash)?;
+        (&mut self.state).set_contract_class(&class_hash, contract_class)?;
+        (&mut self.state).set_compiled_class_hash(class_hash, compiled_class_hash)?;
+        Ok(())
+    }
+}
+
+impl<B: MapBackend, A> Sequencer<GenericState<B>, A> {
+    /// Sets the nonce of the provided address.
+    pub fn set_nonce(&mut self, address: ContractAddress, nonce: Nonce) {
+        self.state.set_nonce(address, nonce);
+    }
+
+    /// Replaces the class of the contract deployed at the provided address.
+    ///
+    /// # Errors
+    ///
+    /// If no contract is deployed at the address or the class isn't declared.
+    pub fn replace_class(
+        &mut self,
+        address: ContractAddress,
+        class_hash: ClassHash,
+    ) -> StateResult<()> {
+        self.state.replace_class(address, class_hash)
+    }
+}
+
+impl<A> Sequencer<PersistentState, A> {
+    /// Creates a sequencer on top of a persistent state. If the state was
+    /// already committed, the block context resumes after the last committed
+    /// block.
+    #[must_use]
+    pub fn resume(mut block_context: BlockContext, state: PersistentState, address: A) -> Self {
+        if let Some((block_number, block_timestamp)) = state.last_block() {
+            block_context.block_number = block_context
+                .block_number
+                .max(BlockNumber(block_number.0.saturating_add(1)));
+            block_context.block_timestamp = block_context.block_timestamp.max(block_timestamp);
+        }
+        Self::new(block_context, state, address)
+    }
+
+    /// Commits the writes of the current block to disk and mines the block.
+    ///
+    /// # Errors
+    ///
+    /// If the commit fails or if the next block context is invalid.
+    pub fn close_block(&mut self) -> Result<(), PersistentStateError> {
+        self.state.commit_block(&self.block_context)?;
+        self.mine_block()?;
+        Ok(())
+    }
+}
+
+/// Returns the address of the account sending the transaction. L1 handler
+/// transactions have no sender and use the zero address.
+pub(crate) fn sender_address(transaction: &Transaction) -> ContractAddress {
+    match transaction {
+        Transaction::AccountTransaction(tx) => match tx {
+            blockifier::transaction::account_transaction::AccountTransaction::Invoke(tx) => {
+                tx.tx.sender_address()
+            }
+            blockifier::transaction::account_transaction::AccountTransaction::Declare(tx) => {
+                tx.tx().sender_address()
+            }
+            blockifier::transaction::account_transaction::AccountTransaction::DeployAccount(tx) => {
+                tx.contract_address
+            }
+        },
+        Transaction::L1HandlerTransaction(_) => ContractAddress::from(0u8),
+    }
 }
 
 impl<S, A> Execution for Sequencer<S, A>
@@ -57,37 +376,40 @@ where
         &mut self,
         transaction: Transaction,
     ) -> TransactionExecutionResult<TransactionExecutionInfo> {
-        let sender_address = match &transaction {
-            Transaction::AccountTransaction(tx) => match tx {
-                blockifier::transaction::account_transaction::AccountTransaction::Invoke(tx) => {
-                    tx.tx.sender_address()
-                }
-                blockifier::transaction::account_transaction::AccountTransaction::Declare(tx) => {
-                    tx.tx().sender_address()
-                }
-                blockifier::transaction::account_transaction::AccountTransaction::DeployAccount(
-                    tx,
-                ) => tx.contract_address,
-            },
-            Transaction::L1HandlerTransaction(_) => ContractAddress::from(0u8),
-        };
+        let sender_address = sender_address(&transaction);
+        for observer in &self.observers {
+            observer.before_transaction(&transaction, &self.block_context);
+        }
 
         let mut cached_state = CachedState::new(&mut self.state, GlobalContractCache::default());
         let charge_fee = false;
-        let validate = true;
+        let validate = !self.impersonated_accounts.contains(&sender_address);
         let res = transaction.execute(&mut cached_state, &self.block_context, charge_fee, validate);
 
         let execution_information = match res {
             Err(err) => {
+                for observer in &self.observers {
+                    observer.on_rejection(&err);
+                }
                 return Err(err);
             }
             Ok(execution_information) => {
                 if execution_information.revert_error.is_some() {
                     // If the transaction reverted, we increment the nonce.
                     (&mut self.state).increment_nonce(sender_address)?;
+                    for observer in &self.observers {
+                        observer.on_revert(&execution_information);
+                    }
                 } else {
                     // If the transaction succeeded, we commit the state.
+                    // The diff is only computed if someone observes it.
+                    let diff = (!self.observers.is_empty()).then(|| cached_state.to_state_diff());
                     <&mut S>::commit(&mut cached_state)?;
+                    if let Some(diff) = diff {
+                        for observer in &self.observers {
+                            observer.after_transaction(&execution_information, &diff);
+                        }
+                    }
                 }
                 execution_information
             }
@@ -99,32 +421,27 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
     use std::fmt::Display;
-    use std::fs::File;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
 
     use blockifier::abi::abi_utils::get_storage_var_address;
-    use blockifier::block_context::{FeeTokenAddresses, GasPrices};
-    use blockifier::execution::contract_class::{ContractClass, ContractClassV0, ContractClassV1};
+    use blockifier::execution::contract_class::{ContractClass, ContractClassV0};
+    use blockifier::state::cached_state::CommitmentStateDiff;
     use blockifier::state::state_api::State as BlockifierState;
     use blockifier::transaction::account_transaction::AccountTransaction;
+    use blockifier::transaction::errors::TransactionExecutionError;
     use blockifier::transaction::transactions::InvokeTransaction as BlockifierInvokeTransaction;
     use starknet::macros::selector;
-    use starknet_api::core::{ChainId, ClassHash, ContractAddress, Nonce};
-    use starknet_api::hash::StarkFelt;
     use starknet_api::transaction::{
         Calldata, Fee, InvokeTransaction, InvokeTransactionV1, TransactionHash,
         TransactionSignature,
     };
 
     use crate::constants::test_constants::{
-        ETH_FEE_TOKEN_ADDRESS, SEQUENCER_ADDRESS, STRK_FEE_TOKEN_ADDRESS,
-    };
-    use crate::constants::test_constants::{
-        ONE_BLOCK_NUMBER, ONE_BLOCK_TIMESTAMP, ONE_CLASS_HASH, TEST_ACCOUNT, TEST_CONTRACT,
-        TWO_CLASS_HASH, ZERO_FELT,
+        block_context, read_contract_class, ETH_FEE_TOKEN_ADDRESS, ONE_BLOCK_NUMBER,
+        ONE_CLASS_HASH, TEST_ACCOUNT, TEST_CONTRACT, TWO_CLASS_HASH, ZERO_FELT,
     };
+    use crate::metrics::{SharedStorageMetrics, StorageAccess};
     use crate::state::State;
 
     use super::*;
@@ -143,79 +460,37 @@ mod tests {
         }
     }
 
-    fn read_contract_class_v0(path: &str) -> ContractClass {
-        let reader = File::open(path).unwrap();
-        let contract_class: ContractClassV0 = serde_json::from_reader(reader).unwrap();
-
-        ContractClass::V0(contract_class)
-    }
-
-    fn read_contract_class_v1(path: &str) -> ContractClass {
-        let raw_contract_class = std::fs::read_to_string(path).unwrap();
-        let contract_class: ContractClassV1 =
-            ContractClassV1::try_from_json_string(&raw_contract_class).unwrap();
-
-        ContractClass::V1(contract_class)
-    }
-
-    fn declare_and_deploy_contract(
-        path: &str,
-        address: ContractAddress,
-        class_hash: ClassHash,
-        mut state: &mut State,
-        version: CairoVersion,
-    ) {
-        let contract_class = match version {
-            CairoVersion::V0 => read_contract_class_v0(path),
-            CairoVersion::V1 => read_contract_class_v1(path),
-        };
-
-        state
-            .set_contract_class(&class_hash, contract_class)
-            .unwrap();
-        state.set_class_hash_at(address, class_hash).unwrap();
-    }
-
-    fn fund(address: StarkFelt, mut state: &mut State) {
-        state.set_storage_at(
-            *ETH_FEE_TOKEN_ADDRESS,
-            get_storage_var_address("ERC20_balances", &[address]),
-            StarkFelt::from(u128::MAX),
-        );
-    }
-
     macro_rules! sequencer_test {
         ($cairo_version: path, $test_name: ident) => {
             #[test]
             fn $test_name() {
                 // Given
-                let mut state = State::default();
-                let mutable = &mut state;
-
-                declare_and_deploy_contract(
-                    &format!(
-                        "src/test_data/{}/compiled_classes/counter.json",
-                        $cairo_version
-                    ),
-                    *TEST_CONTRACT,
-                    *ONE_CLASS_HASH,
-                    mutable,
-                    $cairo_version,
-                );
-                declare_and_deploy_contract(
-                    &format!(
-                        "src/test_data/{}/compiled_classes/account.json",
-                        $cairo_version
-                    ),
-                    *TEST_ACCOUNT,
-                    *TWO_CLASS_HASH,
-                    mutable,
-                    $cairo_version,
-                );
-                fund(*TEST_ACCOUNT.0.key(), mutable);
-
                 let context = block_context();
-                let mut sequencer = Sequencer::new(context, state, 0);
+                let mut sequencer = Sequencer::new(context, State::default(), 0);
+
+                sequencer
+                    .deploy_class_at(
+                        *TEST_CONTRACT,
+                        *ONE_CLASS_HASH,
+                        read_contract_class(&format!(
+                            "src/test_data/{}/compiled_classes/counter.json",
+                            $cairo_version
+                        )),
+                    )
+                    .unwrap();
+                sequencer
+                    .deploy_class_at(
+                        *TEST_ACCOUNT,
+                        *TWO_CLASS_HASH,
+                        read_contract_class(&format!(
+                            "src/test_data/{}/compiled_classes/account.json",
+                            $cairo_version
+                        )),
+                    )
+                    .unwrap();
+                sequencer
+                    .set_fee_token_balance(*TEST_ACCOUNT, [u128::MAX, 0])
+                    .unwrap();
 
                 // When
                 let transaction = test_transaction();
@@ -231,54 +506,205 @@ mod tests {
         };
     }
 
-    fn block_context() -> BlockContext {
-        BlockContext {
-            chain_id: ChainId("KKRT".into()),
-            block_number: *ONE_BLOCK_NUMBER,
-            block_timestamp: *ONE_BLOCK_TIMESTAMP,
-            sequencer_address: *SEQUENCER_ADDRESS,
-            fee_token_addresses: FeeTokenAddresses {
-                strk_fee_token_address: *STRK_FEE_TOKEN_ADDRESS,
-                eth_fee_token_address: *ETH_FEE_TOKEN_ADDRESS,
-            },
-
-            vm_resource_fee_cost: vm_resource_fee_cost(),
-            gas_prices: GasPrices {
-                eth_l1_gas_price: 1,
-                strk_l1_gas_price: 1,
-            },
-            invoke_tx_max_n_steps: 4_000_000,
-            validate_max_n_steps: 4_000_000,
-            max_recursion_depth: 1_000,
+    fn test_transaction() -> Transaction {
+        Transaction::AccountTransaction(AccountTransaction::Invoke(BlockifierInvokeTransaction {
+            tx: InvokeTransaction::V1(InvokeTransactionV1 {
+                sender_address: *TEST_ACCOUNT,
+                calldata: Calldata(
+                    vec![
+                        *TEST_CONTRACT.0.key(), // destination
+                        selector!("inc").into(),
+                        *ZERO_FELT, // no data
+                    ]
+                    .into(),
+                ),
+                max_fee: Fee(1_000_000),
+                signature: TransactionSignature(vec![]),
+                nonce: Nonce(*ZERO_FELT),
+            }),
+            only_query: false,
+            tx_hash: TransactionHash(*ZERO_FELT),
+        }))
+    }
+
+    sequencer_test!(CairoVersion::V0, test_sequencer_cairo_0);
+    sequencer_test!(CairoVersion::V1, test_sequencer_cairo_1);
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl RecordingObserver {
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    impl ExecutionObserver for RecordingObserver {
+        fn before_transaction(&self, _transaction: &Transaction, block_context: &BlockContext) {
+            self.record(format!("before {}", block_context.block_number.0));
+        }
+
+        fn after_transaction(&self, _info: &TransactionExecutionInfo, diff: &CommitmentStateDiff) {
+            self.record(format!("after {}", diff.storage_updates.len()));
+        }
+
+        fn on_revert(&self, _info: &TransactionExecutionInfo) {
+            self.record(String::from("revert"));
+        }
+
+        fn on_rejection(&self, _error: &TransactionExecutionError) {
+            self.record(String::from("rejection"));
+        }
+
+        fn on_block_close(&self, block_context: &BlockContext) {
+            self.record(format!("close {}", block_context.block_number.0));
         }
     }
 
-    /// Maps builtins and steps to a single cost unit of reference (gas).
-    fn vm_resource_fee_cost() -> Arc<HashMap<String, f64>> {
-        Arc::new(
-            [
-                (String::from("n_steps"), 1_f64),
-                ("pedersen_builtin".to_string(), 1_f64),
-                ("range_check_builtin".to_string(), 1_f64),
-                ("ecdsa_builtin".to_string(), 1_f64),
-                ("bitwise_builtin".to_string(), 1_f64),
-                ("poseidon_builtin".to_string(), 1_f64),
-                ("output_builtin".to_string(), 1_f64),
-                ("ec_op_builtin".to_string(), 1_f64),
-                ("keccak_builtin".to_string(), 1_f64),
-                ("segment_arena_builtin".to_string(), 1_f64),
+    #[test]
+    fn test_observer() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        sequencer
+            .deploy_class_at(
+                *TEST_ACCOUNT,
+                *TWO_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/account.json"),
+            )
+            .unwrap();
+        let observer = Arc::new(RecordingObserver::default());
+        sequencer.add_observer(observer.clone());
+
+        // When
+        sequencer.execute(test_transaction()).unwrap();
+        sequencer.mine_block().unwrap();
+        sequencer.execute(test_transaction()).unwrap_err();
+
+        // Then
+        let events = observer.events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                format!("before {}", ONE_BLOCK_NUMBER.0),
+                String::from("after 1"),
+                format!("close {}", ONE_BLOCK_NUMBER.0),
+                format!("before {}", ONE_BLOCK_NUMBER.0 + 1),
+                String::from("rejection"),
             ]
-            .into_iter()
-            .collect(),
-        )
+        );
     }
 
-    fn test_transaction() -> Transaction {
+    #[test]
+    fn test_observer_mine_blocks() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+        let observer = Arc::new(RecordingObserver::default());
+        sequencer.add_observer(observer.clone());
+
+        // When
+        sequencer.mine_blocks(3).unwrap();
+
+        // Then
+        let events = observer.events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            (0..3)
+                .map(|i| format!("close {}", ONE_BLOCK_NUMBER.0 + i))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            sequencer.block_context().block_number,
+            BlockNumber(ONE_BLOCK_NUMBER.0 + 3)
+        );
+    }
+
+    #[test]
+    fn test_storage_metrics() {
+        // Given
+        let metrics = SharedStorageMetrics::new();
+        let state = State::default().with_storage_metrics(metrics.clone());
+        let mut sequencer = Sequencer::new(block_context(), state, 0);
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        sequencer
+            .deploy_class_at(
+                *TEST_ACCOUNT,
+                *TWO_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_0/compiled_classes/account.json"),
+            )
+            .unwrap();
+        let counter = (*TEST_CONTRACT, get_storage_var_address("counter", &[]));
+
+        // When
+        sequencer.execute(test_transaction()).unwrap();
+        let recorded = metrics.take();
+        let mut simulation =
+            CachedState::new(sequencer.state.view(), GlobalContractCache::default());
+        simulation.get_storage_at(counter.0, counter.1).unwrap();
+
+        // Then
+        // The counter is read and written by the transaction, once each.
+        assert_eq!(
+            recorded.slot(&counter),
+            StorageAccess {
+                reads: 1,
+                writes: 1
+            }
+        );
+        assert!(recorded.new_slots() >= 1);
+        assert!(metrics.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_set_fee_token_balance() {
+        // Given
+        let metrics = SharedStorageMetrics::new();
+        let state = State::default().with_storage_metrics(metrics.clone());
+        let mut sequencer = Sequencer::new(block_context(), state, 0);
+
+        // When
+        sequencer
+            .set_fee_token_balance(*TEST_ACCOUNT, [1, 2])
+            .unwrap();
+
+        // Then
+        let low_key = get_fee_token_var_address(&TEST_ACCOUNT);
+        let high_key = next_storage_key(&low_key).unwrap();
+        let low = (&mut sequencer.state)
+            .get_storage_at(*ETH_FEE_TOKEN_ADDRESS, low_key)
+            .unwrap();
+        let high = (&mut sequencer.state)
+            .get_storage_at(*ETH_FEE_TOKEN_ADDRESS, high_key)
+            .unwrap();
+        assert_eq!(low, StarkFelt::from(1u8));
+        assert_eq!(high, StarkFelt::from(2u8));
+        // The balance is set directly, not by a transaction.
+        assert_eq!(metrics.snapshot().total().writes, 0);
+    }
+
+    /// Invokes `inc` on the test contract through the `__execute__` entry
+    /// point of the validating account, which takes an array of calls.
+    fn validating_account_transaction() -> Transaction {
         Transaction::AccountTransaction(AccountTransaction::Invoke(BlockifierInvokeTransaction {
             tx: InvokeTransaction::V1(InvokeTransactionV1 {
                 sender_address: *TEST_ACCOUNT,
                 calldata: Calldata(
                     vec![
+                        StarkFelt::from(1u8),   // number of calls
                         *TEST_CONTRACT.0.key(), // destination
                         selector!("inc").into(),
                         *ZERO_FELT, // no data
@@ -294,6 +720,207 @@ mod tests {
         }))
     }
 
-    sequencer_test!(CairoVersion::V0, test_sequencer_cairo_0);
-    sequencer_test!(CairoVersion::V1, test_sequencer_cairo_1);
+    #[test]
+    fn test_impersonate_account() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                read_contract_class("src/test_data/cairo_1/compiled_classes/counter.json"),
+            )
+            .unwrap();
+        sequencer
+            .deploy_class_at(
+                *TEST_ACCOUNT,
+                *TWO_CLASS_HASH,
+                read_contract_class(
+                    "src/test_data/cairo_1/compiled_classes/validating_account.json",
+                ),
+            )
+            .unwrap();
+        let counter = |sequencer: &mut Sequencer<State, i32>| {
+            (&mut sequencer.state)
+                .get_storage_at(*TEST_CONTRACT, get_storage_var_address("counter", &[]))
+                .unwrap()
+        };
+
+        // When
+        let unsigned = sequencer.execute(validating_account_transaction());
+
+        // Then
+        assert!(matches!(
+            unsigned,
+            Err(TransactionExecutionError::ValidateTransactionError(_))
+        ));
+        assert_eq!(counter(&mut sequencer), *ZERO_FELT);
+
+        // When
+        sequencer.impersonate_account(*TEST_ACCOUNT);
+        let impersonated = sequencer.execute(validating_account_transaction()).unwrap();
+        sequencer.stop_impersonating_account(*TEST_ACCOUNT);
+
+        // Then
+        assert!(impersonated.revert_error.is_none());
+        assert!(!sequencer.is_impersonated(&TEST_ACCOUNT));
+        assert_eq!(counter(&mut sequencer), StarkFelt::from(1u8));
+    }
+
+    #[test]
+    fn test_replace_class() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+        sequencer
+            .deploy_class_at(
+                *TEST_CONTRACT,
+                *ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut sequencer.state)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+
+        // When
+        sequencer
+            .replace_class(*TEST_CONTRACT, *TWO_CLASS_HASH)
+            .unwrap();
+        sequencer.set_nonce(*TEST_CONTRACT, Nonce(StarkFelt::from(5u8)));
+
+        // Then
+        let class_hash = (&mut sequencer.state)
+            .get_class_hash_at(*TEST_CONTRACT)
+            .unwrap();
+        let nonce = (&mut sequencer.state).get_nonce_at(*TEST_CONTRACT).unwrap();
+        assert_eq!(class_hash, *TWO_CLASS_HASH);
+        assert_eq!(nonce, Nonce(StarkFelt::from(5u8)));
+    }
+
+    #[test]
+    fn test_mine_block() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+
+        // When
+        sequencer
+            .set_next_block_timestamp(BlockTimestamp(100))
+            .unwrap();
+        sequencer.mine_block().unwrap();
+        sequencer.mine_blocks(2).unwrap();
+
+        // Then
+        assert_eq!(sequencer.block_context().block_number, BlockNumber(4));
+        assert_eq!(
+            sequencer.block_context().block_timestamp,
+            BlockTimestamp(100)
+        );
+    }
+
+    #[test]
+    fn test_increase_time() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+
+        // When
+        sequencer.increase_time(3600).unwrap();
+        sequencer.mine_block().unwrap();
+
+        // Then
+        assert_eq!(sequencer.block_context().block_number, BlockNumber(2));
+        assert_eq!(
+            sequencer.block_context().block_timestamp,
+            BlockTimestamp(3601)
+        );
+    }
+
+    #[test]
+    fn test_set_block_timestamp_past_next_block_timestamp() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+        sequencer
+            .set_next_block_timestamp(BlockTimestamp(100))
+            .unwrap();
+
+        // When
+        sequencer.set_block_timestamp(BlockTimestamp(200)).unwrap();
+        sequencer.mine_block().unwrap();
+
+        // Then
+        assert_eq!(sequencer.block_context().block_number, BlockNumber(2));
+        assert_eq!(
+            sequencer.block_context().block_timestamp,
+            BlockTimestamp(200)
+        );
+    }
+
+    #[test]
+    fn test_set_block_number() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+
+        // When
+        sequencer.set_block_number(BlockNumber(1_000)).unwrap();
+        let result = sequencer.set_block_number(BlockNumber(999));
+
+        // Then
+        assert_eq!(sequencer.block_context().block_number, BlockNumber(1_000));
+        assert!(matches!(
+            result,
+            Err(BlockContextError::BlockNumberDecrease {
+                current: 1_000,
+                new: 999
+            })
+        ));
+    }
+
+    #[test]
+    fn test_set_gas_prices() {
+        // Given
+        let mut sequencer = Sequencer::new(block_context(), State::default(), 0);
+
+        // When
+        sequencer
+            .set_gas_prices(GasPrices {
+                eth_l1_gas_price: 10,
+                strk_l1_gas_price: 20,
+            })
+            .unwrap();
+
+        // Then
+        assert_eq!(sequencer.block_context().gas_prices.eth_l1_gas_price, 10);
+        assert_eq!(sequencer.block_context().gas_prices.strk_l1_gas_price, 20);
+    }
+
+    #[test]
+    fn test_close_block_and_resume() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.log");
+        let state = PersistentState::open(&path).unwrap();
+        let mut sequencer = Sequencer::resume(block_context(), state, 0);
+        sequencer
+            .set_fee_token_balance(*TEST_ACCOUNT, [1_000, 0])
+            .unwrap();
+
+        // When
+        sequencer.close_block().unwrap();
+        drop(sequencer);
+        let state = PersistentState::open(&path).unwrap();
+        let mut sequencer = Sequencer::resume(block_context(), state, 0);
+
+        // Then
+        assert_eq!(
+            sequencer.block_context().block_number,
+            BlockNumber(ONE_BLOCK_NUMBER.0 + 1)
+        );
+        let fee_token_address = *ETH_FEE_TOKEN_ADDRESS;
+        let balance = (&mut sequencer.state)
+            .get_storage_at(fee_token_address, get_fee_token_var_address(&TEST_ACCOUNT))
+            .unwrap();
+        assert_eq!(balance, StarkFelt::from(1_000u128));
+    }
 }
@@ -1,44 +1,213 @@
-use std::{fs, io, path::Path};
+mod binary;
+pub mod canonical;
+pub mod delta;
+pub mod devnet;
+pub mod katana;
+pub mod migration;
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    marker::PhantomData,
+    path::Path,
+};
 
-use crate::state::State;
+use crate::state::{
+    backend::{FxHashBackend, MapBackend, StateMap},
+    GenericState,
+};
 use blockifier::{
     execution::contract_class::ContractClass, state::cached_state::ContractStorageKey,
 };
-use rustc_hash::FxHashMap;
-use serde::{Deserialize, Serialize};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{
+    de::{Error as _, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Map, Value};
 use starknet_api::{
     core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
     hash::StarkFelt,
+    StarknetApiError,
 };
 
 use thiserror::Error;
 
+/// Magic bytes at the start of every gzip stream.
+const GZIP_MAGIC: &[u8; 2] = &[0x1f, 0x8b];
+
+/// Encoding of a state dump.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Human readable JSON.
+    #[default]
+    Json,
+    /// Versioned compact binary format.
+    Binary,
+}
+
+/// Compression applied to a state dump.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+/// Options used when dumping a state. The format and compression of a dump
+/// are detected when loading it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DumpOptions {
+    pub format: DumpFormat,
+    pub compression: Compression,
+}
+
 pub trait DumpLoad {
-    fn dump_state_to_file(self, file_path: &Path) -> Result<(), SerializationError>;
+    fn dump_state_to_file(self, file_path: &Path) -> Result<(), SerializationError>
+    where
+        Self: Sized,
+    {
+        self.dump_state_to_file_with_options(file_path, DumpOptions::default())
+    }
+
+    fn dump_state_to_file_with_options(
+        self,
+        file_path: &Path,
+        options: DumpOptions,
+    ) -> Result<(), SerializationError>
+    where
+        Self: Sized,
+    {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.dump_state_to_writer(&mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn dump_state_to_writer<W: Write>(
+        self,
+        writer: W,
+        options: DumpOptions,
+    ) -> Result<(), SerializationError>;
 
     fn load_state_from_file(file_path: &Path) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        Self::load_state_from_reader(BufReader::new(File::open(file_path)?))
+    }
+
+    fn load_state_from_reader<R: BufRead>(reader: R) -> Result<Self, SerializationError>
     where
         Self: Sized;
 }
 
-impl DumpLoad for State {
-    /// This will serialize the current state, and will save it to a path
-    fn dump_state_to_file(self, path: &Path) -> Result<(), SerializationError> {
-        let serializable_state: SerializableState = self.into();
+impl<B: MapBackend> DumpLoad for GenericState<B> {
+    /// This will serialize the current state, and will stream it to the writer
+    fn dump_state_to_writer<W: Write>(
+        self,
+        mut writer: W,
+        options: DumpOptions,
+    ) -> Result<(), SerializationError> {
+        let serializable_state: SerializableState<B> = self.into();
+
+        match options.format {
+            DumpFormat::Json => {
+                let dump = VersionedDump {
+                    version: migration::VERSION,
+                    state: serializable_state,
+                };
+                with_compression(writer, options.compression, |writer| {
+                    Ok(serde_json::to_writer(writer, &dump)?)
+                })?;
+            }
+            DumpFormat::Binary => {
+                let compression = match options.compression {
+                    Compression::None => 0u8,
+                    Compression::Gzip => 1u8,
+                };
+                writer.write_all(binary::MAGIC)?;
+                writer.write_all(&[binary::VERSION, compression])?;
+                with_compression(writer, options.compression, |writer| {
+                    binary::write_state(&serializable_state, writer)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
 
-        let dump = serde_json::to_string(&serializable_state)?;
+    /// This will read a dump from a reader and initialize the state from it.
+    /// The format and compression of the dump are detected from its first bytes.
+    fn load_state_from_reader<R: BufRead>(mut reader: R) -> Result<Self, SerializationError> {
+        let serializable_state = read_dump(&mut reader)?;
+        Ok(serializable_state.into())
+    }
+}
 
-        fs::write(path, dump)?;
+/// Reads a dump, detecting its format and compression. The dump is directly
+/// deserialized into the maps of the backend.
+fn read_dump<B: MapBackend>(
+    mut reader: &mut dyn BufRead,
+) -> Result<SerializableState<B>, SerializationError> {
+    let (is_gzip, is_binary) = {
+        let header = reader.fill_buf()?;
+        (
+            header.starts_with(GZIP_MAGIC),
+            header.starts_with(binary::MAGIC),
+        )
+    };
 
-        Ok(())
+    if is_gzip {
+        return read_dump(&mut BufReader::new(GzDecoder::new(reader)));
+    }
+    if !is_binary {
+        return match serde_json::from_reader::<_, JsonDump<B>>(reader)? {
+            JsonDump::Current(state) => Ok(state),
+            JsonDump::Outdated(dump) => {
+                let dump: VersionedDump<B> = serde_json::from_value(migration::migrate(dump)?)?;
+                Ok(dump.state)
+            }
+        };
     }
 
-    /// This will read a dump from a file and initialize the state from it
-    fn load_state_from_file(path: &Path) -> Result<Self, SerializationError> {
-        let dump = fs::read(path)?;
-        let serializable_state: SerializableState = serde_json::from_slice(&dump)?;
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header)?;
+    let [.., version, compression] = header;
+    if version != binary::VERSION {
+        return Err(SerializationError::VersionMismatch {
+            found: u32::from(version),
+            expected: u32::from(binary::VERSION),
+        });
+    }
+    match compression {
+        0 => binary::read_state(&mut reader),
+        1 => binary::read_state(&mut BufReader::new(GzDecoder::new(reader))),
+        _ => Err(SerializationError::InvalidDump(format!(
+            "unknown compression {}",
+            compression
+        ))),
+    }
+}
 
-        Ok(serializable_state.into())
+/// Wraps the writer in the requested compression and calls `f` on it.
+fn with_compression<W: Write>(
+    writer: W,
+    compression: Compression,
+    f: impl FnOnce(&mut dyn Write) -> Result<(), SerializationError>,
+) -> Result<(), SerializationError> {
+    match compression {
+        Compression::None => {
+            let mut writer = writer;
+            f(&mut writer)
+        }
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            f(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
     }
 }
 
@@ -48,36 +217,138 @@ pub enum SerializationError {
     IoError(#[from] io::Error),
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    StarknetApiError(#[from] StarknetApiError),
+    #[error("invalid dump: {0}")]
+    InvalidDump(String),
+    #[error("unsupported dump version {found}, expected version {expected} or older")]
+    VersionMismatch { found: u32, expected: u32 },
+    #[error("content hash mismatch: expected {expected}, got {actual}")]
+    ContentHashMismatch { expected: String, actual: String },
+}
+
+/// JSON dump of the state, along with the version of the format. Older dumps
+/// are upgraded on load, see [`migration`].
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct VersionedDump<B: MapBackend> {
+    version: u32,
+    state: SerializableState<B>,
+}
+
+/// JSON dump read in a single streaming pass. The state of a current dump is
+/// deserialized directly into the maps of the backend. Older dumps are
+/// buffered as a JSON value, which the migrations operate on.
+enum JsonDump<B: MapBackend> {
+    Current(SerializableState<B>),
+    Outdated(Value),
+}
+
+impl<'de, B: MapBackend> Deserialize<'de> for JsonDump<B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(JsonDumpVisitor(PhantomData))
+    }
+}
+
+struct JsonDumpVisitor<B>(PhantomData<B>);
+
+impl<'de, B: MapBackend> Visitor<'de> for JsonDumpVisitor<B> {
+    type Value = JsonDump<B>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON state dump")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let is_current = |version: &Option<Value>| {
+            version.as_ref().and_then(Value::as_u64) == Some(u64::from(migration::VERSION))
+        };
+
+        let mut version: Option<Value> = None;
+        let mut state: Option<JsonDump<B>> = None;
+        let mut fields = Map::new();
+        while let Some(key) = access.next_key::<String>()? {
+            match key.as_str() {
+                "version" if version.is_some() => return Err(A::Error::duplicate_field("version")),
+                "version" => version = Some(access.next_value()?),
+                "state" if state.is_some() => return Err(A::Error::duplicate_field("state")),
+                // The version is written before the state, which can then be
+                // deserialized directly.
+                "state" if is_current(&version) => {
+                    state = Some(JsonDump::Current(access.next_value()?));
+                }
+                "state" => state = Some(JsonDump::Outdated(access.next_value()?)),
+                _ => {
+                    fields.insert(key, access.next_value()?);
+                }
+            }
+        }
+
+        match (state, is_current(&version)) {
+            (Some(JsonDump::Current(state)), _) => Ok(JsonDump::Current(state)),
+            (Some(JsonDump::Outdated(state)), true) => serde_json::from_value(state)
+                .map(JsonDump::Current)
+                .map_err(A::Error::custom),
+            (None, true) => Err(A::Error::missing_field("state")),
+            (state, false) => {
+                if let Some(version) = version {
+                    fields.insert(String::from("version"), version);
+                }
+                if let Some(JsonDump::Outdated(state)) = state {
+                    fields.insert(String::from("state"), state);
+                }
+                Ok(JsonDump::Outdated(Value::Object(fields)))
+            }
+        }
+    }
 }
 
+/// Content of a state dump, held in the maps of the backend of the state it's
+/// dumped from or loaded into.
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
-pub struct SerializableState {
-    pub classes: FxHashMap<ClassHash, ContractClass>,
-    pub compiled_classes_hash: FxHashMap<ClassHash, CompiledClassHash>,
-    pub contracts: FxHashMap<ContractAddress, ClassHash>,
+#[serde(bound = "")]
+pub struct SerializableState<B: MapBackend = FxHashBackend> {
+    pub classes: B::Map<ClassHash, ContractClass>,
+    pub compiled_classes_hash: B::Map<ClassHash, CompiledClassHash>,
+    pub contracts: B::Map<ContractAddress, ClassHash>,
     #[serde(with = "serialize_contract_storage")]
-    pub storage: FxHashMap<ContractStorageKey, StarkFelt>,
-    pub nonces: FxHashMap<ContractAddress, Nonce>,
+    pub storage: B::Map<ContractStorageKey, StarkFelt>,
+    pub nonces: B::Map<ContractAddress, Nonce>,
+}
+
+impl<B: MapBackend> SerializableState<B> {
+    /// Moves the entries to the maps of another backend.
+    pub fn into_backend<C: MapBackend>(self) -> SerializableState<C> {
+        SerializableState {
+            classes: self.classes.into_iter().collect(),
+            compiled_classes_hash: self.compiled_classes_hash.into_iter().collect(),
+            contracts: self.contracts.into_iter().collect(),
+            storage: self.storage.into_iter().collect(),
+            nonces: self.nonces.into_iter().collect(),
+        }
+    }
 }
 
 mod serialize_contract_storage {
     use blockifier::state::cached_state::ContractStorageKey;
-    use rustc_hash::{FxHashMap, FxHasher};
     use serde::de::{Deserializer, MapAccess, Visitor};
     use serde::ser::{SerializeMap, Serializer};
     use starknet_api::hash::StarkFelt;
     use std::fmt;
-    use std::hash::BuildHasherDefault;
+    use std::marker::PhantomData;
 
-    pub fn serialize<S>(
-        map: &FxHashMap<ContractStorageKey, StarkFelt>,
-        serializer: S,
-    ) -> Result<S::Ok, S::Error>
+    use crate::state::backend::StateMap;
+
+    pub fn serialize<S, M>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
+        M: StateMap<ContractStorageKey, StarkFelt>,
     {
         let mut serialized_map = serializer.serialize_map(Some(map.len()))?;
-        for (k, v) in map {
+        for (k, v) in map.iter() {
             let key = serde_json::to_string(k).map_err(|error| {
                 serde::ser::Error::custom(format!(
                     "failed to deserialize contract_storage_key {:?},\n error {}",
@@ -90,20 +361,22 @@ mod serialize_contract_storage {
         serialized_map.end()
     }
 
-    pub fn deserialize<'de, D>(
-        deserializer: D,
-    ) -> Result<FxHashMap<ContractStorageKey, StarkFelt>, D::Error>
+    pub fn deserialize<'de, D, M>(deserializer: D) -> Result<M, D::Error>
     where
         D: Deserializer<'de>,
+        M: StateMap<ContractStorageKey, StarkFelt>,
     {
-        deserializer.deserialize_map(MapContractStorageKeyVisitor)
+        deserializer.deserialize_map(MapContractStorageKeyVisitor(PhantomData))
     }
 
-    struct MapContractStorageKeyVisitor;
+    struct MapContractStorageKeyVisitor<M>(PhantomData<M>);
 
-    impl<'de> Visitor<'de> for MapContractStorageKeyVisitor {
+    impl<'de, M> Visitor<'de> for MapContractStorageKeyVisitor<M>
+    where
+        M: StateMap<ContractStorageKey, StarkFelt>,
+    {
         // The type that our Visitor is going to produce.
-        type Value = FxHashMap<ContractStorageKey, StarkFelt>;
+        type Value = M;
 
         // Format a message stating what data this Visitor expects to receive.
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
@@ -117,10 +390,7 @@ mod serialize_contract_storage {
         where
             M: MapAccess<'de>,
         {
-            let mut map = FxHashMap::with_capacity_and_hasher(
-                access.size_hint().unwrap_or(0),
-                BuildHasherDefault::<FxHasher>::default(),
-            );
+            let mut map = M::with_capacity(access.size_hint().unwrap_or(0));
 
             // While there are entries remaining in the input, add them
             // into our map.
@@ -152,11 +422,10 @@ mod tests {
             ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, TEST_CONTRACT, TEST_NONCE,
             TEST_STORAGE_KEY,
         },
-        state::State,
+        state::{backend::SipHashBackend, State},
     };
 
-    #[test]
-    pub fn dump_and_load_state() {
+    fn test_state() -> State {
         let mut state = State::default();
 
         // setting up entry for state.classes
@@ -182,6 +451,13 @@ mod tests {
         (&mut state).set_storage_at(contract_address, *TEST_STORAGE_KEY, storage_value);
         state.set_nonce(contract_address, nonce);
 
+        state
+    }
+
+    #[test]
+    pub fn dump_and_load_state() {
+        let state = test_state();
+
         let temp_file = tempfile::NamedTempFile::new().expect("failed open named temp file");
         let dump_file_path = temp_file.into_temp_path();
 
@@ -198,4 +474,126 @@ mod tests {
 
         assert_eq!(loaded_state, state);
     }
+
+    macro_rules! dump_and_load_test {
+        ($format: expr, $compression: expr, $test_name: ident) => {
+            #[test]
+            fn $test_name() {
+                // Given
+                let state = test_state();
+                let options = DumpOptions {
+                    format: $format,
+                    compression: $compression,
+                };
+
+                // When
+                let mut dump = Vec::new();
+                state
+                    .clone()
+                    .dump_state_to_writer(&mut dump, options)
+                    .expect("failed to dump state");
+                let loaded_state =
+                    State::load_state_from_reader(dump.as_slice()).expect("failed to load state");
+
+                // Then
+                assert_eq!(state, loaded_state);
+            }
+        };
+    }
+
+    dump_and_load_test!(DumpFormat::Json, Compression::None, dump_and_load_json);
+    dump_and_load_test!(DumpFormat::Json, Compression::Gzip, dump_and_load_json_gzip);
+    dump_and_load_test!(DumpFormat::Binary, Compression::None, dump_and_load_binary);
+    dump_and_load_test!(
+        DumpFormat::Binary,
+        Compression::Gzip,
+        dump_and_load_binary_gzip
+    );
+
+    #[test]
+    fn dump_and_load_sip_hash_state() {
+        // Given
+        let state: GenericState<SipHashBackend> =
+            SerializableState::from(test_state()).into_backend().into();
+
+        for format in [DumpFormat::Json, DumpFormat::Binary] {
+            // When
+            let mut dump = Vec::new();
+            let options = DumpOptions {
+                format,
+                compression: Compression::None,
+            };
+            state
+                .clone()
+                .dump_state_to_writer(&mut dump, options)
+                .expect("failed to dump state");
+            let loaded_state =
+                GenericState::<SipHashBackend>::load_state_from_reader(dump.as_slice())
+                    .expect("failed to load state");
+
+            // Then
+            assert_eq!(state, loaded_state);
+        }
+    }
+
+    #[test]
+    fn load_unsupported_binary_version() {
+        // Given
+        let mut dump = binary::MAGIC.to_vec();
+        dump.extend_from_slice(&[binary::VERSION + 1, 0]);
+
+        // When
+        let result = State::load_state_from_reader(dump.as_slice());
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(SerializationError::VersionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn load_unversioned_json_dump() {
+        // Given
+        let state = test_state();
+        let dump = serde_json::to_vec(&SerializableState::from(state.clone())).unwrap();
+
+        // When
+        let loaded_state = State::load_state_from_reader(dump.as_slice()).unwrap();
+
+        // Then
+        assert_eq!(state, loaded_state);
+    }
+
+    #[test]
+    fn dump_json_has_version() {
+        // Given
+        let mut dump = Vec::new();
+
+        // When
+        test_state()
+            .dump_state_to_writer(&mut dump, DumpOptions::default())
+            .unwrap();
+
+        // Then
+        let dump: serde_json::Value = serde_json::from_slice(&dump).unwrap();
+        assert_eq!(dump["version"], migration::VERSION);
+    }
+
+    #[test]
+    fn load_json_dump_with_version_after_state() {
+        // Given
+        let state = test_state();
+        let dump = format!(
+            r#"{{"state":{},"version":{}}}"#,
+            serde_json::to_string(&SerializableState::from(state.clone())).unwrap(),
+            migration::VERSION
+        );
+
+        // When
+        let loaded_state = State::load_state_from_reader(dump.as_bytes()).unwrap();
+
+        // Then
+        assert_eq!(state, loaded_state);
+    }
 }
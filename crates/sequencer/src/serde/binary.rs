@@ -0,0 +1,155 @@
+use std::io::{Read, Write};
+
+use blockifier::execution::contract_class::ContractClass;
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+
+use super::{SerializableState, SerializationError};
+use crate::state::backend::{MapBackend, StateMap};
+
+/// Magic bytes at the start of every binary dump.
+pub(super) const MAGIC: &[u8; 4] = b"SQST";
+/// Current version of the binary dump format.
+pub(super) const VERSION: u8 = 1;
+
+/// Writes the body of a binary dump. Felts are written as 32 big endian bytes,
+/// lengths as 8 little endian bytes. Contract classes are written as length
+/// prefixed JSON, since their serde implementation is tied to JSON.
+pub(super) fn write_state<B: MapBackend, W: Write + ?Sized>(
+    state: &SerializableState<B>,
+    writer: &mut W,
+) -> Result<(), SerializationError> {
+    write_len(writer, state.classes.len())?;
+    for (class_hash, class) in state.classes.iter() {
+        write_felt(writer, &class_hash.0)?;
+        let class = serde_json::to_vec(class)?;
+        write_len(writer, class.len())?;
+        writer.write_all(&class)?;
+    }
+
+    write_len(writer, state.compiled_classes_hash.len())?;
+    for (class_hash, compiled_class_hash) in state.compiled_classes_hash.iter() {
+        write_felt(writer, &class_hash.0)?;
+        write_felt(writer, &compiled_class_hash.0)?;
+    }
+
+    write_len(writer, state.contracts.len())?;
+    for (address, class_hash) in state.contracts.iter() {
+        write_felt(writer, address.0.key())?;
+        write_felt(writer, &class_hash.0)?;
+    }
+
+    write_len(writer, state.storage.len())?;
+    for ((address, key), value) in state.storage.iter() {
+        write_felt(writer, address.0.key())?;
+        write_felt(writer, key.0.key())?;
+        write_felt(writer, value)?;
+    }
+
+    write_len(writer, state.nonces.len())?;
+    for (address, nonce) in state.nonces.iter() {
+        write_felt(writer, address.0.key())?;
+        write_felt(writer, &nonce.0)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the body of a binary dump.
+pub(super) fn read_state<B: MapBackend, R: Read + ?Sized>(
+    reader: &mut R,
+) -> Result<SerializableState<B>, SerializationError> {
+    let mut state = SerializableState::<B>::default();
+
+    let len = read_len(reader)?;
+    state.classes = map_with_capacity(len);
+    for _ in 0..len {
+        let class_hash = ClassHash(read_felt(reader)?);
+        let class_len = read_len(reader)?;
+        let mut class = Vec::new();
+        Read::take(&mut *reader, class_len as u64).read_to_end(&mut class)?;
+        if class.len() != class_len {
+            return Err(SerializationError::InvalidDump(
+                "truncated contract class".into(),
+            ));
+        }
+        let class: ContractClass = serde_json::from_slice(&class)?;
+        state.classes.insert(class_hash, class);
+    }
+
+    let len = read_len(reader)?;
+    state.compiled_classes_hash = map_with_capacity(len);
+    for _ in 0..len {
+        let class_hash = ClassHash(read_felt(reader)?);
+        let compiled_class_hash = CompiledClassHash(read_felt(reader)?);
+        state
+            .compiled_classes_hash
+            .insert(class_hash, compiled_class_hash);
+    }
+
+    let len = read_len(reader)?;
+    state.contracts = map_with_capacity(len);
+    for _ in 0..len {
+        let address = read_address(reader)?;
+        let class_hash = ClassHash(read_felt(reader)?);
+        state.contracts.insert(address, class_hash);
+    }
+
+    let len = read_len(reader)?;
+    state.storage = map_with_capacity(len);
+    for _ in 0..len {
+        let address = read_address(reader)?;
+        let key = StorageKey(PatriciaKey::try_from(read_felt(reader)?)?);
+        let value = read_felt(reader)?;
+        state.storage.insert((address, key), value);
+    }
+
+    let len = read_len(reader)?;
+    state.nonces = map_with_capacity(len);
+    for _ in 0..len {
+        let address = read_address(reader)?;
+        let nonce = Nonce(read_felt(reader)?);
+        state.nonces.insert(address, nonce);
+    }
+
+    Ok(state)
+}
+
+/// Caps the preallocated capacity, so that a corrupted length doesn't
+/// lead to a huge allocation.
+fn map_with_capacity<K, V, M: StateMap<K, V>>(len: usize) -> M {
+    M::with_capacity(len.min(1 << 16))
+}
+
+fn write_len<W: Write + ?Sized>(writer: &mut W, len: usize) -> Result<(), SerializationError> {
+    writer.write_all(&(len as u64).to_le_bytes())?;
+    Ok(())
+}
+
+fn read_len<R: Read + ?Sized>(reader: &mut R) -> Result<usize, SerializationError> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    usize::try_from(u64::from_le_bytes(buf))
+        .map_err(|_| SerializationError::InvalidDump("length overflow".into()))
+}
+
+fn write_felt<W: Write + ?Sized>(
+    writer: &mut W,
+    felt: &StarkFelt,
+) -> Result<(), SerializationError> {
+    writer.write_all(felt.bytes())?;
+    Ok(())
+}
+
+fn read_felt<R: Read + ?Sized>(reader: &mut R) -> Result<StarkFelt, SerializationError> {
+    let mut buf = [0u8; 32];
+    reader.read_exact(&mut buf)?;
+    Ok(StarkFelt::new(buf)?)
+}
+
+fn read_address<R: Read + ?Sized>(reader: &mut R) -> Result<ContractAddress, SerializationError> {
+    Ok(ContractAddress(PatriciaKey::try_from(read_felt(reader)?)?))
+}
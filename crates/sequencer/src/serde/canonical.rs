@@ -0,0 +1,302 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use blockifier::execution::contract_class::ContractClass;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+
+use super::{SerializableState, SerializationError};
+use crate::state::{backend::MapBackend, GenericState};
+
+/// Canonical representation of the state. All maps are sorted by key and all
+/// felts are formatted as lowercase hexadecimal strings without leading zeros,
+/// so that two identical states always produce the same dump.
+/// Classes are only referenced by their hash: their content is stored in a
+/// separate directory, one file per class.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CanonicalState {
+    pub classes: Vec<String>,
+    pub compiled_class_hashes: BTreeMap<String, String>,
+    pub contracts: BTreeMap<String, String>,
+    pub storage: BTreeMap<String, BTreeMap<String, String>>,
+    pub nonces: BTreeMap<String, String>,
+}
+
+impl<B: MapBackend> From<&GenericState<B>> for CanonicalState {
+    fn from(state: &GenericState<B>) -> Self {
+        let mut classes: Vec<_> = state.classes().map(|(k, _)| felt_to_hex(&k.0)).collect();
+        classes.sort();
+
+        let mut storage: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        for ((address, key), value) in state.storage() {
+            storage
+                .entry(felt_to_hex(address.0.key()))
+                .or_default()
+                .insert(felt_to_hex(key.0.key()), felt_to_hex(value));
+        }
+
+        Self {
+            classes,
+            compiled_class_hashes: state
+                .compiled_class_hashes()
+                .map(|(k, v)| (felt_to_hex(&k.0), felt_to_hex(&v.0)))
+                .collect(),
+            contracts: state
+                .contracts()
+                .map(|(k, v)| (felt_to_hex(k.0.key()), felt_to_hex(&v.0)))
+                .collect(),
+            storage,
+            nonces: state
+                .nonces()
+                .map(|(k, v)| (felt_to_hex(k.0.key()), felt_to_hex(&v.0)))
+                .collect(),
+        }
+    }
+}
+
+impl CanonicalState {
+    /// Converts the canonical state back to a state, loading each class with
+    /// the provided function.
+    ///
+    /// # Errors
+    ///
+    /// If a felt is invalid or if a class fails to load.
+    pub fn into_state<B: MapBackend>(
+        self,
+        mut load_class: impl FnMut(ClassHash) -> Result<ContractClass, SerializationError>,
+    ) -> Result<GenericState<B>, SerializationError> {
+        let mut state = SerializableState::<B>::default();
+
+        for class_hash in self.classes {
+            let class_hash = ClassHash(hex_to_felt(&class_hash)?);
+            state.classes.insert(class_hash, load_class(class_hash)?);
+        }
+        for (k, v) in self.compiled_class_hashes {
+            state.compiled_classes_hash.insert(
+                ClassHash(hex_to_felt(&k)?),
+                CompiledClassHash(hex_to_felt(&v)?),
+            );
+        }
+        for (k, v) in self.contracts {
+            state
+                .contracts
+                .insert(hex_to_address(&k)?, ClassHash(hex_to_felt(&v)?));
+        }
+        for (address, slots) in self.storage {
+            let address = hex_to_address(&address)?;
+            for (k, v) in slots {
+                let key = StorageKey(PatriciaKey::try_from(hex_to_felt(&k)?)?);
+                state.storage.insert((address, key), hex_to_felt(&v)?);
+            }
+        }
+        for (k, v) in self.nonces {
+            state
+                .nonces
+                .insert(hex_to_address(&k)?, Nonce(hex_to_felt(&v)?));
+        }
+
+        Ok(state.into())
+    }
+
+    /// Returns the canonical JSON of the state, pretty printed.
+    ///
+    /// # Errors
+    ///
+    /// If the serialization fails.
+    pub fn to_json(&self) -> Result<String, SerializationError> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Returns the SHA-256 of the canonical JSON of the state, as an hexadecimal
+    /// string. Since classes are referenced by their hash, the content hash
+    /// doesn't depend on how the classes were serialized.
+    ///
+    /// # Errors
+    ///
+    /// If the serialization fails.
+    pub fn content_hash(&self) -> Result<String, SerializationError> {
+        let digest = Sha256::digest(self.to_json()?.as_bytes());
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// Dumps the state in canonical form to `path`. The classes are written to
+/// `classes_dir`, in a file named after their class hash. Class files that
+/// already exist are not rewritten.
+///
+/// # Errors
+///
+/// If the serialization or the writing of a file fails.
+pub fn dump_canonical_state<B: MapBackend>(
+    state: &GenericState<B>,
+    path: &Path,
+    classes_dir: &Path,
+) -> Result<(), SerializationError> {
+    fs::create_dir_all(classes_dir)?;
+    for (class_hash, class) in state.classes() {
+        let class_path = class_path(classes_dir, class_hash);
+        if class_path.exists() {
+            continue;
+        }
+        // Going through a `serde_json::Value` sorts the keys of all objects.
+        let class = serde_json::to_value(class)?;
+        let mut writer = BufWriter::new(File::create(class_path)?);
+        serde_json::to_writer_pretty(&mut writer, &class)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+
+    fs::write(path, CanonicalState::from(state).to_json()?)?;
+    Ok(())
+}
+
+/// Loads a state dumped in canonical form, reading its classes from
+/// `classes_dir`.
+///
+/// # Errors
+///
+/// If a file is missing or fails to deserialize.
+pub fn load_canonical_state<B: MapBackend>(
+    path: &Path,
+    classes_dir: &Path,
+) -> Result<GenericState<B>, SerializationError> {
+    let canonical: CanonicalState = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    canonical.into_state(|class_hash| {
+        let reader = BufReader::new(File::open(class_path(classes_dir, &class_hash))?);
+        Ok(serde_json::from_reader(reader)?)
+    })
+}
+
+/// Returns the path of the class file in the classes directory.
+pub fn class_path(classes_dir: &Path, class_hash: &ClassHash) -> PathBuf {
+    classes_dir.join(format!("{}.json", felt_to_hex(&class_hash.0)))
+}
+
+/// Formats the felt as a lowercase hexadecimal string without leading zeros.
+pub fn felt_to_hex(felt: &StarkFelt) -> String {
+    let hex: String = felt.bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    let hex = hex.trim_start_matches('0');
+    if hex.is_empty() {
+        String::from("0x0")
+    } else {
+        format!("0x{}", hex)
+    }
+}
+
+fn hex_to_felt(hex: &str) -> Result<StarkFelt, SerializationError> {
+    Ok(StarkFelt::try_from(hex)?)
+}
+
+fn hex_to_address(hex: &str) -> Result<ContractAddress, SerializationError> {
+    Ok(ContractAddress(PatriciaKey::try_from(hex_to_felt(hex)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{execution::contract_class::ContractClassV0, state::state_api::State as _};
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT,
+        TEST_CONTRACT, TWO_FELT, TWO_PATRICIA,
+    };
+    use crate::state::State;
+
+    use super::*;
+
+    fn test_state(reverse: bool) -> State {
+        let mut state = State::default();
+        let mut slots = vec![
+            (*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT),
+            (*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *TWO_FELT),
+            (*TEST_ACCOUNT, StorageKey(*ONE_PATRICIA), *TWO_FELT),
+        ];
+        if reverse {
+            slots.reverse();
+        }
+        for (address, key, value) in slots {
+            (&mut state).set_storage_at(address, key, value);
+        }
+
+        let contract_class = include_str!("../test_data/cairo_0/compiled_classes/counter.json");
+        let contract_class: ContractClassV0 = serde_json::from_str(contract_class).unwrap();
+        (&mut state)
+            .set_contract_class(&ONE_CLASS_HASH, ContractClass::V0(contract_class))
+            .unwrap();
+        (&mut state)
+            .set_compiled_class_hash(*ONE_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        state.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+
+        state
+    }
+
+    #[test]
+    fn test_felt_to_hex() {
+        assert_eq!(felt_to_hex(&StarkFelt::from(0u8)), "0x0");
+        assert_eq!(felt_to_hex(&StarkFelt::from(0x1au8)), "0x1a");
+    }
+
+    #[test]
+    fn test_canonical_json_is_deterministic() {
+        // Given
+        let state = test_state(false);
+        let reversed = test_state(true);
+
+        // When
+        let json = CanonicalState::from(&state).to_json().unwrap();
+        let reversed_json = CanonicalState::from(&reversed).to_json().unwrap();
+
+        // Then
+        assert_eq!(json, reversed_json);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        // Given
+        let state = test_state(false);
+        let reversed = test_state(true);
+        let mut other = test_state(false);
+        other.set_nonce(*TEST_ACCOUNT, Nonce(*TWO_FELT));
+
+        // When
+        let hash = CanonicalState::from(&state).content_hash().unwrap();
+        let reversed_hash = CanonicalState::from(&reversed).content_hash().unwrap();
+        let other_hash = CanonicalState::from(&other).content_hash().unwrap();
+
+        // Then
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, reversed_hash);
+        assert_ne!(hash, other_hash);
+    }
+
+    #[test]
+    fn test_dump_and_load_canonical_state() {
+        // Given
+        let state = test_state(false);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let classes_dir = dir.path().join("classes");
+
+        // When
+        dump_canonical_state(&state, &path, &classes_dir).unwrap();
+        let loaded = load_canonical_state(&path, &classes_dir).unwrap();
+
+        // Then
+        assert_eq!(state, loaded);
+        assert!(class_path(&classes_dir, &ONE_CLASS_HASH).exists());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use blockifier::execution::contract_class::ContractClass;
+use serde::{Deserialize, Serialize};
+use starknet_api::core::ClassHash;
+
+use super::{canonical::CanonicalState, SerializableState, SerializationError};
+use crate::{
+    diff::StateDiff,
+    state::{backend::MapBackend, GenericState},
+};
+
+/// Delta of a state against a base snapshot. The base snapshot is identified
+/// by its content hash (see [`CanonicalState::content_hash`]), and only the
+/// classes that were added or changed on top of the base are stored.
+///
+/// Computing a content hash serializes the full state, classes included, so
+/// callers that already know the hash of the base (e.g. the result of the
+/// previous delta of a chain) should pass it in with
+/// [`StateDelta::new_with_base_hash`] and [`StateDelta::apply_with_base_hash`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDelta {
+    /// Content hash of the base snapshot.
+    pub base: String,
+    /// Content hash of the state obtained after applying the delta.
+    pub result: String,
+    pub diff: StateDiff,
+    pub classes: BTreeMap<ClassHash, ContractClass>,
+}
+
+impl StateDelta {
+    /// Computes the delta from the base to the state.
+    ///
+    /// # Errors
+    ///
+    /// If the content hash of one of the states fails to compute.
+    pub fn new<B: MapBackend>(
+        base: &GenericState<B>,
+        state: &GenericState<B>,
+    ) -> Result<Self, SerializationError> {
+        let base_hash = CanonicalState::from(base).content_hash()?;
+        Self::new_with_base_hash(base, base_hash, state)
+    }
+
+    /// Computes the delta from the base, whose content hash is already known,
+    /// to the state.
+    ///
+    /// # Errors
+    ///
+    /// If the content hash of the state fails to compute.
+    pub fn new_with_base_hash<B: MapBackend>(
+        base: &GenericState<B>,
+        base_hash: String,
+        state: &GenericState<B>,
+    ) -> Result<Self, SerializationError> {
+        let diff = base.diff(state);
+        let classes = diff
+            .classes
+            .added
+            .iter()
+            .chain(diff.classes.changed.iter())
+            .filter_map(|class_hash| {
+                state
+                    .class(class_hash)
+                    .map(|class| (*class_hash, class.clone()))
+            })
+            .collect();
+
+        Ok(Self {
+            base: base_hash,
+            result: CanonicalState::from(state).content_hash()?,
+            diff,
+            classes,
+        })
+    }
+
+    /// Applies the delta on top of the base.
+    ///
+    /// # Errors
+    ///
+    /// If the content hash of the base or of the resulting state doesn't match
+    /// the one recorded in the delta.
+    pub fn apply<B: MapBackend>(
+        &self,
+        base: GenericState<B>,
+    ) -> Result<GenericState<B>, SerializationError> {
+        let base_hash = CanonicalState::from(&base).content_hash()?;
+        self.apply_with_base_hash(base, &base_hash)
+    }
+
+    /// Applies the delta on top of a base whose content hash is already known,
+    /// without hashing the base again.
+    ///
+    /// # Errors
+    ///
+    /// If the provided content hash of the base or the content hash of the
+    /// resulting state doesn't match the one recorded in the delta.
+    pub fn apply_with_base_hash<B: MapBackend>(
+        &self,
+        base: GenericState<B>,
+        base_hash: &str,
+    ) -> Result<GenericState<B>, SerializationError> {
+        if base_hash != self.base {
+            return Err(SerializationError::ContentHashMismatch {
+                expected: self.base.clone(),
+                actual: base_hash.to_string(),
+            });
+        }
+
+        let mut state: SerializableState<B> = base.into();
+        let diff = &self.diff;
+
+        for (address, _) in &diff.contracts.removed {
+            state.contracts.remove(address);
+        }
+        for (address, class_hash) in &diff.contracts.added {
+            state.contracts.insert(*address, *class_hash);
+        }
+        for (address, change) in &diff.contracts.changed {
+            state.contracts.insert(*address, change.after);
+        }
+
+        for (key, _) in &diff.storage.removed {
+            state.storage.remove(key);
+        }
+        for (key, value) in &diff.storage.added {
+            state.storage.insert(*key, *value);
+        }
+        for (key, change) in &diff.storage.changed {
+            state.storage.insert(*key, change.after);
+        }
+
+        for (address, _) in &diff.nonces.removed {
+            state.nonces.remove(address);
+        }
+        for (address, nonce) in &diff.nonces.added {
+            state.nonces.insert(*address, *nonce);
+        }
+        for (address, change) in &diff.nonces.changed {
+            state.nonces.insert(*address, change.after);
+        }
+
+        for class_hash in &diff.classes.removed {
+            state.classes.remove(class_hash);
+        }
+        for (class_hash, class) in &self.classes {
+            state.classes.insert(*class_hash, class.clone());
+        }
+
+        for (class_hash, _) in &diff.compiled_class_hashes.removed {
+            state.compiled_classes_hash.remove(class_hash);
+        }
+        for (class_hash, compiled_class_hash) in &diff.compiled_class_hashes.added {
+            state
+                .compiled_classes_hash
+                .insert(*class_hash, *compiled_class_hash);
+        }
+        for (class_hash, change) in &diff.compiled_class_hashes.changed {
+            state
+                .compiled_classes_hash
+                .insert(*class_hash, change.after);
+        }
+
+        let state: GenericState<B> = state.into();
+        check_hash(&self.result, &state)?;
+        Ok(state)
+    }
+}
+
+fn check_hash<B: MapBackend>(
+    expected: &str,
+    state: &GenericState<B>,
+) -> Result<(), SerializationError> {
+    let actual = CanonicalState::from(state).content_hash()?;
+    if actual != expected {
+        return Err(SerializationError::ContentHashMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Dumps the delta from the base to the state to a file.
+///
+/// # Errors
+///
+/// If the serialization or the writing of the file fails.
+pub fn dump_delta_to_file<B: MapBackend>(
+    base: &GenericState<B>,
+    state: &GenericState<B>,
+    path: &Path,
+) -> Result<(), SerializationError> {
+    let delta = StateDelta::new(base, state)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &delta)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Loads a delta from a file.
+///
+/// # Errors
+///
+/// If the reading or the deserialization of the file fails.
+pub fn load_delta_from_file(path: &Path) -> Result<StateDelta, SerializationError> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// Loads a state by applying a chain of deltas, in order, on top of the base.
+/// Only the base is hashed, each following delta is checked against the
+/// verified result of the previous one.
+///
+/// # Errors
+///
+/// If a delta fails to load or doesn't apply to the state it's applied on.
+pub fn load_state_with_deltas<B: MapBackend>(
+    base: GenericState<B>,
+    delta_paths: &[&Path],
+) -> Result<GenericState<B>, SerializationError> {
+    let mut state = base;
+    let mut state_hash = None;
+    for path in delta_paths {
+        let delta = load_delta_from_file(path)?;
+        state = match &state_hash {
+            Some(hash) => delta.apply_with_base_hash(state, hash)?,
+            None => delta.apply(state)?,
+        };
+        state_hash = Some(delta.result);
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{execution::contract_class::ContractClassV0, state::state_api::State as _};
+    use starknet_api::{core::Nonce, state::StorageKey};
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT, TEST_CONTRACT, TWO_CLASS_HASH,
+        TWO_FELT, TWO_PATRICIA,
+    };
+    use crate::state::State;
+
+    use super::*;
+
+    fn base_state() -> State {
+        let mut state = State::default();
+        (&mut state)
+            .set_contract_class(
+                &ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *ONE_FELT);
+        state
+    }
+
+    fn counter_class() -> ContractClass {
+        let contract_class = include_str!("../test_data/cairo_0/compiled_classes/counter.json");
+        ContractClass::V0(serde_json::from_str(contract_class).unwrap())
+    }
+
+    #[test]
+    fn test_delta_chain() {
+        // Given
+        let base = base_state();
+
+        let mut first = base.clone();
+        (&mut first).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        first.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+
+        let mut second = first.clone();
+        (&mut second)
+            .set_contract_class(&TWO_CLASS_HASH, counter_class())
+            .unwrap();
+        (&mut second)
+            .set_class_hash_at(*TEST_ACCOUNT, *TWO_CLASS_HASH)
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let first_path = dir.path().join("first.json");
+        let second_path = dir.path().join("second.json");
+
+        // When
+        dump_delta_to_file(&base, &first, &first_path).unwrap();
+        dump_delta_to_file(&first, &second, &second_path).unwrap();
+        let loaded =
+            load_state_with_deltas(base, &[first_path.as_path(), second_path.as_path()]).unwrap();
+
+        // Then
+        assert_eq!(loaded, second);
+    }
+
+    #[test]
+    fn test_delta_wrong_base() {
+        // Given
+        let base = base_state();
+        let mut state = base.clone();
+        state.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+        let delta = StateDelta::new(&base, &state).unwrap();
+
+        // When
+        let result = delta.apply(State::default());
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(SerializationError::ContentHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_delta_with_base_hash() {
+        // Given
+        let base = base_state();
+        let mut first = base.clone();
+        first.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+        let mut second = first.clone();
+        second.set_nonce(*TEST_ACCOUNT, Nonce(*TWO_FELT));
+        let first_delta = StateDelta::new(&base, &first).unwrap();
+
+        // When
+        let second_delta =
+            StateDelta::new_with_base_hash(&first, first_delta.result.clone(), &second).unwrap();
+        let state = first_delta.apply(base.clone()).unwrap();
+        let state = second_delta
+            .apply_with_base_hash(state, &first_delta.result)
+            .unwrap();
+        let wrong_base = second_delta.apply_with_base_hash(base, &first_delta.base);
+
+        // Then
+        assert_eq!(second_delta, StateDelta::new(&first, &second).unwrap());
+        assert_eq!(state, second);
+        assert!(matches!(
+            wrong_base,
+            Err(SerializationError::ContentHashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_delta_only_stores_new_classes() {
+        // Given
+        let base = base_state();
+        let mut state = base.clone();
+        (&mut state)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+
+        // When
+        let delta = StateDelta::new(&base, &state).unwrap();
+
+        // Then
+        assert_eq!(
+            delta.classes.keys().collect::<Vec<_>>(),
+            vec![&*TWO_CLASS_HASH]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use blockifier::execution::contract_class::ContractClass;
+use starknet::core::types::{
+    ContractStorageDiffItem, DeclaredClassItem, DeployedContractItem, FieldElement, NonceUpdate,
+    StateDiff, StorageEntry,
+};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+
+use super::{SerializableState, SerializationError};
+use crate::state::{backend::MapBackend, GenericState};
+
+/// Converts the state to the state diff served by starknet-devnet-rs through
+/// `starknet_getStateUpdate`. Dumps of starknet-devnet-rs are transaction logs
+/// which are replayed on load, so the state update is the format used to move
+/// a state between the devnet and the sequencer. Classes are only referenced
+/// by their hash: Cairo 0 classes are listed as deprecated declared classes,
+/// Cairo 1 classes as declared classes with their compiled class hash.
+///
+/// # Errors
+///
+/// If a felt fails to convert or if a Cairo 1 class has no compiled class
+/// hash.
+pub fn state_to_devnet_state_diff<B: MapBackend>(
+    state: &GenericState<B>,
+) -> Result<StateDiff, SerializationError> {
+    let mut storage: BTreeMap<ContractAddress, Vec<StorageEntry>> = BTreeMap::new();
+    for ((address, key), value) in state.storage().collect::<BTreeMap<_, _>>() {
+        storage.entry(*address).or_default().push(StorageEntry {
+            key: to_field_element(key.0.key())?,
+            value: to_field_element(value)?,
+        });
+    }
+    let storage_diffs = storage
+        .into_iter()
+        .map(|(address, storage_entries)| {
+            Ok(ContractStorageDiffItem {
+                address: to_field_element(address.0.key())?,
+                storage_entries,
+            })
+        })
+        .collect::<Result<_, SerializationError>>()?;
+
+    let mut deprecated_declared_classes = Vec::new();
+    let mut declared_classes = Vec::new();
+    for (class_hash, class) in state.classes().collect::<BTreeMap<_, _>>() {
+        match (class, state.compiled_class_hash_of(class_hash)) {
+            (ContractClass::V1(_), Some(compiled_class_hash)) => {
+                declared_classes.push(DeclaredClassItem {
+                    class_hash: to_field_element(&class_hash.0)?,
+                    compiled_class_hash: to_field_element(&compiled_class_hash.0)?,
+                });
+            }
+            (ContractClass::V0(_), _) => {
+                deprecated_declared_classes.push(to_field_element(&class_hash.0)?)
+            }
+            (ContractClass::V1(_), None) => {
+                return Err(SerializationError::InvalidDump(format!(
+                    "missing compiled class hash of the Cairo 1 class {}",
+                    class_hash.0
+                )))
+            }
+        }
+    }
+
+    let deployed_contracts = state
+        .contracts()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(address, class_hash)| {
+            Ok(DeployedContractItem {
+                address: to_field_element(address.0.key())?,
+                class_hash: to_field_element(&class_hash.0)?,
+            })
+        })
+        .collect::<Result<_, SerializationError>>()?;
+
+    let nonces = state
+        .nonces()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(address, nonce)| {
+            Ok(NonceUpdate {
+                contract_address: to_field_element(address.0.key())?,
+                nonce: to_field_element(&nonce.0)?,
+            })
+        })
+        .collect::<Result<_, SerializationError>>()?;
+
+    Ok(StateDiff {
+        storage_diffs,
+        deprecated_declared_classes,
+        declared_classes,
+        deployed_contracts,
+        replaced_classes: Vec::new(),
+        nonces,
+    })
+}
+
+/// Builds a state from a starknet-devnet-rs state diff, loading each declared
+/// class with the provided function. Replaced classes are applied after the
+/// deployed contracts.
+///
+/// # Errors
+///
+/// If a felt is invalid or if a class fails to load.
+pub fn devnet_state_diff_to_state<B: MapBackend>(
+    state_diff: StateDiff,
+    mut load_class: impl FnMut(ClassHash) -> Result<ContractClass, SerializationError>,
+) -> Result<GenericState<B>, SerializationError> {
+    let mut state = SerializableState::<B>::default();
+
+    for class_hash in state_diff.deprecated_declared_classes {
+        let class_hash = ClassHash(class_hash.into());
+        state.classes.insert(class_hash, load_class(class_hash)?);
+    }
+    for item in state_diff.declared_classes {
+        let class_hash = ClassHash(item.class_hash.into());
+        state.classes.insert(class_hash, load_class(class_hash)?);
+        state.compiled_classes_hash.insert(
+            class_hash,
+            CompiledClassHash(item.compiled_class_hash.into()),
+        );
+    }
+    for item in state_diff.deployed_contracts {
+        state
+            .contracts
+            .insert(to_address(item.address)?, ClassHash(item.class_hash.into()));
+    }
+    for item in state_diff.replaced_classes {
+        state.contracts.insert(
+            to_address(item.contract_address)?,
+            ClassHash(item.class_hash.into()),
+        );
+    }
+    for item in state_diff.storage_diffs {
+        let address = to_address(item.address)?;
+        for entry in item.storage_entries {
+            let key = StorageKey(PatriciaKey::try_from(StarkFelt::from(entry.key))?);
+            state.storage.insert((address, key), entry.value.into());
+        }
+    }
+    for item in state_diff.nonces {
+        state
+            .nonces
+            .insert(to_address(item.contract_address)?, Nonce(item.nonce.into()));
+    }
+
+    Ok(state.into())
+}
+
+/// Dumps the state as a starknet-devnet-rs state diff to `path`.
+///
+/// # Errors
+///
+/// If the conversion, the serialization or the writing of the file fails.
+pub fn dump_devnet_state_diff<B: MapBackend>(
+    state: &GenericState<B>,
+    path: &Path,
+) -> Result<(), SerializationError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &state_to_devnet_state_diff(state)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Loads a state from a starknet-devnet-rs state diff stored at `path`,
+/// loading each declared class with the provided function.
+///
+/// # Errors
+///
+/// If the reading, the deserialization or the conversion fails.
+pub fn load_devnet_state_diff<B: MapBackend>(
+    path: &Path,
+    load_class: impl FnMut(ClassHash) -> Result<ContractClass, SerializationError>,
+) -> Result<GenericState<B>, SerializationError> {
+    let state_diff: StateDiff = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    devnet_state_diff_to_state(state_diff, load_class)
+}
+
+fn to_field_element(felt: &StarkFelt) -> Result<FieldElement, SerializationError> {
+    FieldElement::from_bytes_be(felt.bytes())
+        .map_err(|_| SerializationError::InvalidDump(format!("invalid field element {}", felt)))
+}
+
+fn to_address(felt: FieldElement) -> Result<ContractAddress, SerializationError> {
+    Ok(ContractAddress(PatriciaKey::try_from(StarkFelt::from(
+        felt,
+    ))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{
+        execution::contract_class::{ContractClassV0, ContractClassV1},
+        state::state_api::State as _,
+    };
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT, TEST_CONTRACT, TWO_CLASS_HASH,
+        TWO_FELT,
+    };
+    use crate::state::State;
+
+    use super::*;
+
+    fn test_state() -> State {
+        let mut state = State::default();
+        (&mut state)
+            .set_contract_class(
+                &ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.set_nonce(*TEST_CONTRACT, Nonce(*TWO_FELT));
+        state
+    }
+
+    #[test]
+    fn test_devnet_state_diff_round_trip() {
+        // Given
+        let state = test_state();
+
+        // When
+        let state_diff = state_to_devnet_state_diff(&state).unwrap();
+        let loaded = devnet_state_diff_to_state(state_diff.clone(), |class_hash| {
+            Ok(state.class(&class_hash).unwrap().clone())
+        })
+        .unwrap();
+
+        // Then
+        assert_eq!(
+            state_diff.deprecated_declared_classes,
+            vec![to_field_element(&ONE_CLASS_HASH.0).unwrap()]
+        );
+        assert_eq!(state_diff.storage_diffs.len(), 1);
+        assert_eq!(state, loaded);
+    }
+
+    #[test]
+    fn test_devnet_replaced_classes() {
+        // Given
+        let mut state_diff = state_to_devnet_state_diff(&test_state()).unwrap();
+        state_diff
+            .replaced_classes
+            .push(starknet::core::types::ReplacedClassItem {
+                contract_address: to_field_element(TEST_CONTRACT.0.key()).unwrap(),
+                class_hash: to_field_element(&TWO_CLASS_HASH.0).unwrap(),
+            });
+        state_diff.deployed_contracts.push(DeployedContractItem {
+            address: to_field_element(TEST_ACCOUNT.0.key()).unwrap(),
+            class_hash: to_field_element(&ONE_CLASS_HASH.0).unwrap(),
+        });
+
+        // When
+        let state: State = devnet_state_diff_to_state(state_diff, |_| {
+            Ok(ContractClass::V0(ContractClassV0::default()))
+        })
+        .unwrap();
+
+        // Then
+        assert_eq!(state.class_hash_of(&TEST_CONTRACT), Some(&*TWO_CLASS_HASH));
+        assert_eq!(state.class_hash_of(&TEST_ACCOUNT), Some(&*ONE_CLASS_HASH));
+    }
+
+    #[test]
+    fn test_devnet_state_diff_missing_compiled_class_hash() {
+        // Given
+        let mut state = test_state();
+        let class = ContractClassV1::try_from_json_string(include_str!(
+            "../test_data/cairo_1/compiled_classes/counter.json"
+        ))
+        .unwrap();
+        (&mut state)
+            .set_contract_class(&TWO_CLASS_HASH, ContractClass::V1(class))
+            .unwrap();
+
+        // When
+        let result = state_to_devnet_state_diff(&state);
+
+        // Then
+        assert!(matches!(result, Err(SerializationError::InvalidDump(_))));
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use blockifier::execution::contract_class::ContractClass;
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use starknet_api::{
+    core::{ClassHash, CompiledClassHash, ContractAddress, Nonce},
+    hash::StarkFelt,
+    state::StorageKey,
+};
+
+use super::{SerializableState, SerializationError, GZIP_MAGIC};
+use crate::state::{backend::MapBackend, GenericState};
+
+/// State dump of Katana, as written by `katana --dump-state` and read by
+/// `katana --load-state`. Contracts are indexed by address and hold their
+/// class hash, nonce and storage.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KatanaState {
+    pub storage: BTreeMap<ContractAddress, KatanaStorageRecord>,
+    pub classes: BTreeMap<ClassHash, KatanaClassRecord>,
+    /// Sierra classes are kept as is: the sequencer only executes the
+    /// compiled classes, which are stored in `classes`.
+    #[serde(default)]
+    pub sierra_classes: BTreeMap<ClassHash, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KatanaStorageRecord {
+    pub nonce: Nonce,
+    pub class_hash: ClassHash,
+    pub storage: BTreeMap<StorageKey, StarkFelt>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KatanaClassRecord {
+    pub class: ContractClass,
+    pub compiled_hash: CompiledClassHash,
+}
+
+impl<B: MapBackend> From<&GenericState<B>> for KatanaState {
+    fn from(state: &GenericState<B>) -> Self {
+        let mut storage: BTreeMap<ContractAddress, KatanaStorageRecord> = BTreeMap::new();
+        for (address, class_hash) in state.contracts() {
+            storage.entry(*address).or_default().class_hash = *class_hash;
+        }
+        for (address, nonce) in state.nonces() {
+            storage.entry(*address).or_default().nonce = *nonce;
+        }
+        for ((address, key), value) in state.storage() {
+            storage
+                .entry(*address)
+                .or_default()
+                .storage
+                .insert(*key, *value);
+        }
+
+        let classes = state
+            .classes()
+            .map(|(class_hash, class)| {
+                let compiled_hash = state
+                    .compiled_class_hash_of(class_hash)
+                    .copied()
+                    .unwrap_or_default();
+                (
+                    *class_hash,
+                    KatanaClassRecord {
+                        class: class.clone(),
+                        compiled_hash,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            storage,
+            classes,
+            sierra_classes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<B: MapBackend> From<KatanaState> for GenericState<B> {
+    fn from(katana: KatanaState) -> Self {
+        let mut state = SerializableState::<B>::default();
+
+        for (address, record) in katana.storage {
+            // Katana uses the zero class hash and the zero nonce for addresses
+            // which only hold storage.
+            if record.class_hash != ClassHash::default() {
+                state.contracts.insert(address, record.class_hash);
+            }
+            if record.nonce != Nonce::default() {
+                state.nonces.insert(address, record.nonce);
+            }
+            for (key, value) in record.storage {
+                state.storage.insert((address, key), value);
+            }
+        }
+        for (class_hash, record) in katana.classes {
+            if record.compiled_hash != CompiledClassHash::default() {
+                state
+                    .compiled_classes_hash
+                    .insert(class_hash, record.compiled_hash);
+            }
+            state.classes.insert(class_hash, record.class);
+        }
+
+        state.into()
+    }
+}
+
+/// Dumps the state in the Katana format, as gzip compressed JSON.
+///
+/// # Errors
+///
+/// If the serialization or the writing of the file fails.
+pub fn dump_katana_state<B: MapBackend>(
+    state: &GenericState<B>,
+    path: &Path,
+) -> Result<(), SerializationError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+    serde_json::to_writer(&mut encoder, &KatanaState::from(state))?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Loads a state dumped in the Katana format. Both compressed and plain JSON
+/// dumps are accepted.
+///
+/// # Errors
+///
+/// If the reading or the deserialization of the file fails.
+pub fn load_katana_state<B: MapBackend>(
+    path: &Path,
+) -> Result<GenericState<B>, SerializationError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let katana: KatanaState = if reader.fill_buf()?.starts_with(GZIP_MAGIC) {
+        serde_json::from_reader(GzDecoder::new(reader))?
+    } else {
+        serde_json::from_reader(reader)?
+    };
+    Ok(katana.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::{execution::contract_class::ContractClassV0, state::state_api::State as _};
+
+    use crate::constants::test_constants::{
+        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT,
+        TEST_CONTRACT, TWO_FELT,
+    };
+    use crate::state::State;
+
+    use super::*;
+
+    fn test_state() -> State {
+        let mut state = State::default();
+        let contract_class = include_str!("../test_data/cairo_0/compiled_classes/counter.json");
+        let contract_class: ContractClassV0 = serde_json::from_str(contract_class).unwrap();
+        (&mut state)
+            .set_contract_class(&ONE_CLASS_HASH, ContractClass::V0(contract_class))
+            .unwrap();
+        (&mut state)
+            .set_compiled_class_hash(*ONE_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        (&mut state).set_storage_at(*TEST_ACCOUNT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        state.set_nonce(*TEST_CONTRACT, Nonce(*ONE_FELT));
+        state
+    }
+
+    #[test]
+    fn test_katana_state_records() {
+        // Given
+        let state = test_state();
+
+        // When
+        let katana = KatanaState::from(&state);
+
+        // Then
+        let record = &katana.storage[&*TEST_CONTRACT];
+        assert_eq!(record.class_hash, *ONE_CLASS_HASH);
+        assert_eq!(record.nonce, Nonce(*ONE_FELT));
+        assert_eq!(record.storage[&StorageKey(*ONE_PATRICIA)], *ONE_FELT);
+        assert_eq!(
+            katana.classes[&*ONE_CLASS_HASH].compiled_hash,
+            *ONE_COMPILED_CLASS_HASH
+        );
+    }
+
+    #[test]
+    fn test_dump_and_load_katana_state() {
+        // Given
+        let state = test_state();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("katana.gz");
+
+        // When
+        dump_katana_state(&state, &path).unwrap();
+        let loaded = load_katana_state(&path).unwrap();
+
+        // Then
+        assert_eq!(state, loaded);
+    }
+}
@@ -0,0 +1,130 @@
+use serde_json::{Map, Value};
+
+use super::SerializationError;
+
+/// Current version of the JSON dump format. Dumps are written as
+/// `{"version": VERSION, "state": <state>}`.
+pub const VERSION: u32 = 1;
+
+/// Version assigned to the dumps written before the format was versioned,
+/// which only contain the state.
+pub const UNVERSIONED: u32 = 0;
+
+/// Migration of a dump from version `from` to version `from + 1`.
+pub struct Migration {
+    pub from: u32,
+    pub migrate: fn(Value) -> Result<Value, SerializationError>,
+}
+
+/// Registry of the migrations, ordered by version. Changes to the layout of
+/// the dump, or to the serde implementation of one of the types it contains,
+/// must bump [`VERSION`] and register the migration from the previous version.
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: UNVERSIONED,
+    migrate: wrap_unversioned,
+}];
+
+/// Returns the version of the dump.
+///
+/// # Errors
+///
+/// If the dump isn't an object or if its version isn't a valid integer.
+pub fn version_of(dump: &Value) -> Result<u32, SerializationError> {
+    let object = dump
+        .as_object()
+        .ok_or_else(|| SerializationError::InvalidDump(String::from("dump isn't an object")))?;
+    match object.get("version") {
+        None => Ok(UNVERSIONED),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| SerializationError::InvalidDump(format!("invalid version {}", version))),
+    }
+}
+
+/// Upgrades the dump to the current version by applying the registered
+/// migrations in order.
+///
+/// # Errors
+///
+/// If the dump is more recent than the current version, if no migration is
+/// registered for one of the intermediate versions or if a migration fails.
+pub fn migrate(mut dump: Value) -> Result<Value, SerializationError> {
+    let mut version = version_of(&dump)?;
+    if version > VERSION {
+        return Err(SerializationError::VersionMismatch {
+            found: version,
+            expected: VERSION,
+        });
+    }
+
+    while version < VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or(SerializationError::VersionMismatch {
+                found: version,
+                expected: VERSION,
+            })?;
+        dump = (migration.migrate)(dump)?;
+        version += 1;
+    }
+
+    Ok(dump)
+}
+
+/// Wraps an unversioned dump in the version 1 envelope.
+fn wrap_unversioned(dump: Value) -> Result<Value, SerializationError> {
+    let mut envelope = Map::new();
+    envelope.insert(String::from("version"), Value::from(1));
+    envelope.insert(String::from("state"), dump);
+    Ok(Value::Object(envelope))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_migrate_unversioned_dump() {
+        // Given
+        let dump = json!({"classes": {}, "nonces": {}});
+
+        // When
+        let migrated = migrate(dump.clone()).unwrap();
+
+        // Then
+        assert_eq!(version_of(&migrated).unwrap(), VERSION);
+        assert_eq!(migrated["state"], dump);
+    }
+
+    #[test]
+    fn test_migrate_current_dump() {
+        // Given
+        let dump = json!({"version": VERSION, "state": {}});
+
+        // When
+        let migrated = migrate(dump.clone()).unwrap();
+
+        // Then
+        assert_eq!(migrated, dump);
+    }
+
+    #[test]
+    fn test_migrate_future_dump() {
+        // Given
+        let dump = json!({"version": VERSION + 1, "state": {}});
+
+        // When
+        let result = migrate(dump);
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(SerializationError::VersionMismatch { found, expected })
+                if found == VERSION + 1 && expected == VERSION
+        ));
+    }
+}
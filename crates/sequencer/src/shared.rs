@@ -0,0 +1,318 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use blockifier::{
+    execution::{
+        call_info::CallInfo,
+        entry_point::{CallEntryPoint, EntryPointExecutionContext, EntryPointExecutionResult},
+    },
+    state::cached_state::{CachedState, GlobalContractCache},
+    transaction::{
+        objects::{
+            AccountTransactionContext, TransactionExecutionInfo, TransactionExecutionResult,
+        },
+        transaction_execution::Transaction,
+        transactions::ExecutableTransaction,
+    },
+};
+use cairo_vm::vm::runners::cairo_runner::ExecutionResources;
+use thiserror::Error;
+
+use crate::{
+    block_context::BlockContextError,
+    execution::Execution,
+    sequencer::{sender_address, Sequencer},
+    state::{
+        backend::{FxHashBackend, MapBackend},
+        GenericState,
+    },
+};
+
+/// Error of [`SharedSequencer::execute_block`] when the block fails to be
+/// mined. The transactions were executed nonetheless, and their results are
+/// returned along with the error.
+#[derive(Error, Debug)]
+#[error("failed to mine the block: {error}")]
+pub struct ExecuteBlockError {
+    pub results: Vec<TransactionExecutionResult<TransactionExecutionInfo>>,
+    #[source]
+    pub error: BlockContextError,
+}
+
+/// Handle to a sequencer shared between threads. Any number of readers can
+/// access the sequencer at the same time, while execution takes exclusive
+/// access. Readers therefore always see a consistent state: blocks executed
+/// through [`SharedSequencer::execute_block`] are never observed partially
+/// executed.
+///
+/// Simulations and calls run against a snapshot of the sequencer, without
+/// holding the lock, so that they never block the execution. The snapshot
+/// is published on the first simulation or call following a write, and is
+/// shared by all the simulations and calls until the next write.
+///
+/// Publishing a snapshot clones the whole sequencer, state included. The
+/// compiled classes are reference counted and aren't copied, but every entry
+/// of the maps of the state is. On large states, such as a Kakarot genesis,
+/// alternating writes and simulations therefore copies the state on each
+/// simulation following a write: batch the writes, through
+/// [`SharedSequencer::execute_block`], or run the simulations on
+/// [`SharedSequencer::read`] when the state is too large to be copied.
+///
+/// Cloning the handle doesn't clone the sequencer.
+pub struct SharedSequencer<A, B: MapBackend = FxHashBackend> {
+    inner: Arc<RwLock<Sequencer<GenericState<B>, A>>>,
+    snapshot: Arc<Mutex<Option<Arc<Sequencer<GenericState<B>, A>>>>>,
+}
+
+impl<A, B: MapBackend> Clone for SharedSequencer<A, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            snapshot: Arc::clone(&self.snapshot),
+        }
+    }
+}
+
+impl<A, B: MapBackend> From<Sequencer<GenericState<B>, A>> for SharedSequencer<A, B> {
+    fn from(sequencer: Sequencer<GenericState<B>, A>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(sequencer)),
+            snapshot: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl<A, B: MapBackend> SharedSequencer<A, B> {
+    /// Takes a read lock on the sequencer, blocking while a transaction or a
+    /// block is executed. A poisoned lock is recovered, since transactions
+    /// are only committed to the state once successfully executed.
+    pub fn read(&self) -> RwLockReadGuard<'_, Sequencer<GenericState<B>, A>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes a write lock on the sequencer, blocking until all the readers
+    /// are done. The published snapshot is discarded, since the sequencer
+    /// can be modified through the lock.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Sequencer<GenericState<B>, A>> {
+        let sequencer = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        *self.lock_snapshot() = None;
+        sequencer
+    }
+
+    fn lock_snapshot(&self) -> MutexGuard<'_, Option<Arc<Sequencer<GenericState<B>, A>>>> {
+        self.snapshot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Executes the transaction on the current block.
+    ///
+    /// # Errors
+    ///
+    /// If the transaction is rejected.
+    pub fn execute(
+        &self,
+        transaction: Transaction,
+    ) -> TransactionExecutionResult<TransactionExecutionInfo> {
+        self.write().execute(transaction)
+    }
+
+    /// Executes the transactions then mines the block, under a single write
+    /// lock. Returns the result of each transaction.
+    ///
+    /// # Errors
+    ///
+    /// If the next block context is invalid. The transactions are executed
+    /// nonetheless, and their results are part of the error.
+    pub fn execute_block(
+        &self,
+        transactions: Vec<Transaction>,
+    ) -> Result<Vec<TransactionExecutionResult<TransactionExecutionInfo>>, ExecuteBlockError> {
+        let mut sequencer = self.write();
+        let results = transactions
+            .into_iter()
+            .map(|transaction| sequencer.execute(transaction))
+            .collect();
+        match sequencer.mine_block() {
+            Ok(()) => Ok(results),
+            Err(error) => Err(ExecuteBlockError { results, error }),
+        }
+    }
+}
+
+impl<A: Clone, B: MapBackend> SharedSequencer<A, B> {
+    /// Returns a snapshot of the sequencer, publishing it if the sequencer
+    /// was written since the last snapshot. Holding the snapshot doesn't
+    /// block the execution.
+    ///
+    /// Publishing the snapshot clones the sequencer and the entries of its
+    /// state, see [`SharedSequencer`].
+    pub fn snapshot(&self) -> Arc<Sequencer<GenericState<B>, A>> {
+        if let Some(snapshot) = self.lock_snapshot().as_ref() {
+            return Arc::clone(snapshot);
+        }
+        // The snapshot is published under the read lock, so that a writer
+        // can't discard it before it's published.
+        let sequencer = self.read();
+        let mut snapshot = self.lock_snapshot();
+        Arc::clone(snapshot.get_or_insert_with(|| Arc::new(sequencer.clone())))
+    }
+
+    /// Executes the transaction against the current state without committing
+    /// it. Transactions sent from impersonated accounts skip validation, as
+    /// in [`Sequencer`]. Observers are not called.
+    ///
+    /// Runs on [`SharedSequencer::snapshot`]: the first simulation or call
+    /// following a write copies the state.
+    ///
+    /// # Errors
+    ///
+    /// If the transaction is rejected.
+    pub fn simulate(
+        &self,
+        transaction: Transaction,
+    ) -> TransactionExecutionResult<TransactionExecutionInfo> {
+        let sequencer = self.snapshot();
+        let validate = !sequencer.is_impersonated(&sender_address(&transaction));
+        let mut cached_state =
+            CachedState::new(sequencer.state.view(), GlobalContractCache::default());
+        let charge_fee = false;
+        transaction.execute(
+            &mut cached_state,
+            sequencer.block_context(),
+            charge_fee,
+            validate,
+        )
+    }
+
+    /// Calls the entry point against the current state without committing
+    /// the writes of the call.
+    ///
+    /// Runs on [`SharedSequencer::snapshot`]: the first simulation or call
+    /// following a write copies the state.
+    ///
+    /// # Errors
+    ///
+    /// If the call fails.
+    pub fn call(&self, call: CallEntryPoint) -> EntryPointExecutionResult<CallInfo> {
+        let sequencer = self.snapshot();
+        let mut cached_state =
+            CachedState::new(sequencer.state.view(), GlobalContractCache::default());
+        let mut resources = ExecutionResources::default();
+        let mut context = EntryPointExecutionContext::new_invoke(
+            sequencer.block_context(),
+            &AccountTransactionContext::default(),
+        );
+        call.execute(&mut cached_state, &mut resources, &mut context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockifier::abi::abi_utils::get_storage_var_address;
+    use starknet_api::block::BlockNumber;
+    use starknet_api::hash::StarkFelt;
+
+    use crate::constants::test_constants::{
+        counter_sequencer, increment, ONE_BLOCK_NUMBER, TEST_CONTRACT, ZERO_FELT,
+    };
+
+    use super::*;
+
+    fn counter(shared: &SharedSequencer<()>) -> StarkFelt {
+        shared
+            .read()
+            .state
+            .storage_of(&TEST_CONTRACT, &get_storage_var_address("counter", &[]))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn shared_sequencer() -> SharedSequencer<()> {
+        SharedSequencer::from(counter_sequencer())
+    }
+
+    #[test]
+    fn test_shared_sequencer() {
+        // Given
+        let shared = shared_sequencer();
+
+        // When
+        let simulations: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let shared = shared.clone();
+                    scope.spawn(move || shared.simulate(increment(0)))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        // Then
+        assert!(simulations.iter().all(|simulation| simulation
+            .as_ref()
+            .unwrap()
+            .revert_error
+            .is_none()));
+        assert_eq!(counter(&shared), *ZERO_FELT);
+
+        // When
+        let results = shared
+            .execute_block(vec![increment(0), increment(1)])
+            .unwrap();
+
+        // Then
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(counter(&shared), StarkFelt::from(2u8));
+        assert_eq!(
+            shared.read().block_context().block_number,
+            BlockNumber(ONE_BLOCK_NUMBER.0 + 1)
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        // Given
+        let shared = shared_sequencer();
+        let snapshot = shared.snapshot();
+
+        // When
+        shared.execute(increment(0)).unwrap();
+
+        // Then
+        let counter_key = get_storage_var_address("counter", &[]);
+        assert!(!Arc::ptr_eq(&snapshot, &shared.snapshot()));
+        assert_eq!(
+            snapshot.state.storage_of(&TEST_CONTRACT, &counter_key),
+            None
+        );
+        assert_eq!(
+            shared
+                .snapshot()
+                .state
+                .storage_of(&TEST_CONTRACT, &counter_key),
+            Some(&StarkFelt::from(1u8))
+        );
+        assert!(Arc::ptr_eq(&shared.snapshot(), &shared.snapshot()));
+    }
+
+    #[test]
+    fn test_execute_block_mine_failure() {
+        // Given
+        let shared = shared_sequencer();
+        shared
+            .write()
+            .set_block_number(BlockNumber(u64::MAX))
+            .unwrap();
+
+        // When
+        let result = shared.execute_block(vec![increment(0)]);
+
+        // Then
+        let error = result.unwrap_err();
+        assert_eq!(error.error, BlockContextError::BlockNumberOverflow);
+        assert_eq!(error.results.len(), 1);
+        assert!(error.results[0].is_ok());
+        assert_eq!(counter(&shared), StarkFelt::from(1u8));
+    }
+}
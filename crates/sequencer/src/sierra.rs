@@ -0,0 +1,206 @@
+use std::sync::Mutex;
+
+use blockifier::{
+    execution::{
+        contract_class::{ContractClass, ContractClassV1},
+        execution_utils::felt_to_stark_felt,
+    },
+    state::errors::StateError,
+};
+use cairo_lang_starknet::{
+    casm_contract_class::{CasmContractClass, StarknetSierraCompilationError},
+    contract_class::ContractClass as SierraContractClass,
+};
+use cairo_vm::types::errors::program_errors::ProgramError;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use starknet_api::core::CompiledClassHash;
+use thiserror::Error;
+
+/// Digest of the parts of a Sierra class the compilation depends on.
+type ContentKey = [u8; 32];
+
+/// Number of compiled classes kept in the process wide cache, beyond which
+/// the least recently used class is evicted.
+const COMPILED_CLASSES_CAPACITY: usize = 128;
+
+lazy_static! {
+    /// Cache of the compiled Sierra classes, indexed by the digest of their
+    /// content, so that a class can't be served the compilation of another
+    /// one. The cache is shared by all the sequencers of the process, so that
+    /// a class is only compiled once per test binary, and holds at most
+    /// [`COMPILED_CLASSES_CAPACITY`] classes.
+    static ref COMPILED_CLASSES: Mutex<LruCache<ContentKey, (ContractClass, CompiledClassHash)>> =
+        Mutex::new(LruCache::new(COMPILED_CLASSES_CAPACITY));
+}
+
+#[derive(Error, Debug)]
+pub enum SierraCompilationError {
+    #[error(transparent)]
+    CompilationError(#[from] StarknetSierraCompilationError),
+    #[error(transparent)]
+    ProgramError(#[from] ProgramError),
+    #[error(transparent)]
+    StateError(#[from] StateError),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("compiled class hash mismatch: declared {declared:?}, computed {computed:?}")]
+    CompiledClassHashMismatch {
+        declared: CompiledClassHash,
+        computed: CompiledClassHash,
+    },
+}
+
+/// Returns the digest of the Sierra program, entry points and version of the
+/// class. The ABI and the debug information don't change the compiled class.
+fn content_key(sierra_class: &SierraContractClass) -> Result<ContentKey, serde_json::Error> {
+    let content = serde_json::to_vec(&(
+        &sierra_class.sierra_program,
+        &sierra_class.entry_points_by_type,
+        &sierra_class.contract_class_version,
+    ))?;
+    Ok(Sha256::digest(content).into())
+}
+
+/// Compiles the Sierra class to CASM and returns it along with its compiled
+/// class hash. Compiled classes are cached by content.
+///
+/// # Errors
+///
+/// If the compilation fails.
+pub fn compile_sierra_class_with_hash(
+    sierra_class: SierraContractClass,
+) -> Result<(ContractClass, CompiledClassHash), SierraCompilationError> {
+    let key = content_key(&sierra_class)?;
+    // A poisoned lock only means another thread panicked while compiling, in
+    // which case we fall back to compiling the class again.
+    let cached = COMPILED_CLASSES
+        .lock()
+        .ok()
+        .and_then(|mut cache| cache.get(&key).cloned());
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+
+    // The class is compiled without holding the lock, so that compilations
+    // of different classes don't wait on each other.
+    let (contract_class, compiled_class_hash) = compile_sierra_class_uncached(sierra_class)?;
+    if let Ok(mut cache) = COMPILED_CLASSES.lock() {
+        cache.put(key, (contract_class.clone(), compiled_class_hash));
+    }
+    Ok((contract_class, compiled_class_hash))
+}
+
+/// Compiles the Sierra class to CASM and returns it along with its compiled
+/// class hash, without going through the process wide cache. Callers holding
+/// many classes, such as the class registry, bound their own cache instead.
+///
+/// # Errors
+///
+/// If the compilation fails.
+pub fn compile_sierra_class_uncached(
+    sierra_class: SierraContractClass,
+) -> Result<(ContractClass, CompiledClassHash), SierraCompilationError> {
+    let casm_class = CasmContractClass::from_contract_class(sierra_class, false)?;
+    let compiled_class_hash =
+        CompiledClassHash(felt_to_stark_felt(&casm_class.compiled_class_hash()));
+    let contract_class = ContractClass::V1(ContractClassV1::try_from(casm_class)?);
+    Ok((contract_class, compiled_class_hash))
+}
+
+/// Compiles the Sierra class to CASM and checks that the hash of the compiled
+/// class matches the declared compiled class hash. Compiled classes are cached
+/// by content.
+///
+/// # Errors
+///
+/// If the compilation fails or if the compiled class hash doesn't match the
+/// declared one.
+pub fn compile_sierra_class(
+    sierra_class: SierraContractClass,
+    compiled_class_hash: CompiledClassHash,
+) -> Result<ContractClass, SierraCompilationError> {
+    let (contract_class, computed) = compile_sierra_class_with_hash(sierra_class)?;
+
+    if computed != compiled_class_hash {
+        return Err(SierraCompilationError::CompiledClassHashMismatch {
+            declared: compiled_class_hash,
+            computed,
+        });
+    }
+
+    Ok(contract_class)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::test_constants::ONE_COMPILED_CLASS_HASH;
+
+    use super::*;
+
+    fn minimal_contract() -> serde_json::Value {
+        let raw_class = include_str!("./test_data/cairo_1/sierra_classes/minimal_contract.json");
+        serde_json::from_str(raw_class).unwrap()
+    }
+
+    #[test]
+    fn test_compile_sierra_class() {
+        // Given
+        let sierra_class: SierraContractClass = serde_json::from_value(minimal_contract()).unwrap();
+        let mut renamed_class = minimal_contract();
+        renamed_class["entry_points_by_type"]["EXTERNAL"][0]["selector"] = "0x1".into();
+        let renamed_class: SierraContractClass = serde_json::from_value(renamed_class).unwrap();
+
+        // When
+        let (contract_class, compiled_class_hash) =
+            compile_sierra_class_with_hash(sierra_class.clone()).unwrap();
+        let recompiled = compile_sierra_class(sierra_class, compiled_class_hash).unwrap();
+        let (_, renamed_compiled_class_hash) =
+            compile_sierra_class_with_hash(renamed_class).unwrap();
+
+        // Then
+        assert_eq!(recompiled, contract_class);
+        assert_ne!(renamed_compiled_class_hash, compiled_class_hash);
+    }
+
+    #[test]
+    fn test_compile_sierra_class_hash_mismatch() {
+        // Given
+        let sierra_class: SierraContractClass = serde_json::from_value(minimal_contract()).unwrap();
+
+        // When
+        let result = compile_sierra_class(sierra_class, *ONE_COMPILED_CLASS_HASH);
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(SierraCompilationError::CompiledClassHashMismatch { declared, .. })
+                if declared == *ONE_COMPILED_CLASS_HASH
+        ));
+    }
+
+    #[test]
+    fn test_compile_invalid_sierra_class() {
+        // Given
+        let sierra_class: SierraContractClass = serde_json::from_str(
+            r#"{
+                "sierra_program": [],
+                "sierra_program_debug_info": null,
+                "contract_class_version": "0.1.0",
+                "entry_points_by_type": {"EXTERNAL": [], "L1_HANDLER": [], "CONSTRUCTOR": []},
+                "abi": null
+            }"#,
+        )
+        .unwrap();
+
+        // When
+        let result = compile_sierra_class(sierra_class, *ONE_COMPILED_CLASS_HASH);
+
+        // Then
+        assert!(matches!(
+            result,
+            Err(SierraCompilationError::CompilationError(_))
+        ));
+    }
+}
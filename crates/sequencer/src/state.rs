@@ -1,4 +1,8 @@
-use blockifier::state::cached_state::CommitmentStateDiff;
+pub mod backend;
+
+use std::fmt::{self, Display};
+
+use blockifier::state::cached_state::{CachedState, CommitmentStateDiff};
 use blockifier::state::errors::StateError;
 use blockifier::state::state_api::{
     State as BlockifierState, StateReader as BlockifierStateReader, StateResult,
@@ -6,7 +10,6 @@ use blockifier::state::state_api::{
 use blockifier::{
     execution::contract_class::ContractClass, state::cached_state::ContractStorageKey,
 };
-use rustc_hash::FxHashMap;
 use starknet_api::core::CompiledClassHash;
 use starknet_api::state::StorageKey;
 use starknet_api::{
@@ -16,26 +19,61 @@ use starknet_api::{
 
 use serde::{Deserialize, Serialize};
 
-use crate::commit::Committer;
+use crate::class_registry::SharedClassRegistry;
+use crate::commit::{apply_state_diff, Committer};
+use crate::metrics::SharedStorageMetrics;
 use crate::serde::SerializableState;
 
-/// Generic state structure for the sequencer.
-/// The use of `FxHashMap` allows for a better performance.
+use self::backend::{FxHashBackend, MapBackend, StateMap};
+
+/// Generic state structure for the sequencer, over the map backend `B`.
+/// See [`State`] for the default backend, and [`backend`] for the available
+/// backends: `SipHashBackend` should be used when the state is built from
+/// untrusted input, `BTreeBackend` when a deterministic iteration order is
+/// needed.
+/// Classes which aren't declared in the state can be loaded lazily from a
+/// [`crate::class_registry::ClassRegistry`]. Those classes are not part of
+/// the state dumps.
+/// Storage accesses can be recorded by attaching
+/// [`crate::metrics::SharedStorageMetrics`] to the state.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct GenericState<B: MapBackend> {
+    classes: B::Map<ClassHash, ContractClass>,
+    compiled_class_hashes: B::Map<ClassHash, CompiledClassHash>,
+    contracts: B::Map<ContractAddress, ClassHash>,
+    storage: B::Map<ContractStorageKey, StarkFelt>,
+    nonces: B::Map<ContractAddress, Nonce>,
+    #[serde(skip)]
+    class_registry: Option<SharedClassRegistry>,
+    #[serde(skip)]
+    storage_metrics: Option<SharedStorageMetrics>,
+}
+
+/// Two states are equal if they hold the same entries. The class registry and
+/// the storage metrics attached to a state aren't part of its content and are
+/// ignored, so that a state equals its reloaded dump.
+impl<B: MapBackend> PartialEq for GenericState<B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.classes == other.classes
+            && self.compiled_class_hashes == other.compiled_class_hashes
+            && self.contracts == other.contracts
+            && self.storage == other.storage
+            && self.nonces == other.nonces
+    }
+}
+
+impl<B: MapBackend> Eq for GenericState<B> {}
+
+/// State of the sequencer, backed by `FxHashMap`.
 /// This hash map is used by rustc. It uses a non cryptographic hash function
-/// which is faster than the default hash function. Think about changing
-/// if the test sequencer is used for tests outside of ef-tests.
+/// which is faster than the default hash function, but is vulnerable to
+/// hash flooding.
 /// See [rustc-hash](https://crates.io/crates/rustc-hash) for more information.
-#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, Eq)]
-pub struct State {
-    classes: FxHashMap<ClassHash, ContractClass>,
-    compiled_class_hashes: FxHashMap<ClassHash, CompiledClassHash>,
-    contracts: FxHashMap<ContractAddress, ClassHash>,
-    storage: FxHashMap<ContractStorageKey, StarkFelt>,
-    nonces: FxHashMap<ContractAddress, Nonce>,
-}
+pub type State = GenericState<FxHashBackend>;
 
-impl From<State> for SerializableState {
-    fn from(state: State) -> Self {
+impl<B: MapBackend> From<GenericState<B>> for SerializableState<B> {
+    fn from(state: GenericState<B>) -> Self {
         Self {
             classes: state.classes,
             compiled_classes_hash: state.compiled_class_hashes,
@@ -46,30 +84,410 @@ impl From<State> for SerializableState {
     }
 }
 
-impl From<SerializableState> for State {
-    fn from(serializable_state: SerializableState) -> Self {
+impl<B: MapBackend> From<SerializableState<B>> for GenericState<B> {
+    fn from(serializable_state: SerializableState<B>) -> Self {
         Self {
             classes: serializable_state.classes,
             compiled_class_hashes: serializable_state.compiled_classes_hash,
             contracts: serializable_state.contracts,
             storage: serializable_state.storage,
             nonces: serializable_state.nonces,
+            class_registry: None,
+            storage_metrics: None,
         }
     }
 }
 
-impl State {
+impl<B: MapBackend> GenericState<B> {
     /// Helper function allowing to set the nonce of a contract.
     pub fn set_nonce(&mut self, contract_address: ContractAddress, nonce: Nonce) {
         self.nonces.insert(contract_address, nonce);
     }
+
+    /// Helper function allowing to replace the class of a deployed contract,
+    /// keeping its storage and nonce.
+    ///
+    /// # Errors
+    ///
+    /// If no contract is deployed at the address or the class isn't declared.
+    pub fn replace_class(
+        &mut self,
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+    ) -> StateResult<()> {
+        if !self.is_declared(&class_hash) {
+            return Err(StateError::UndeclaredClassHash(class_hash));
+        }
+        match self.contracts.get_mut(&contract_address) {
+            Some(current) => {
+                *current = class_hash;
+                Ok(())
+            }
+            None => Err(StateError::StateReadError(format!(
+                "No contract deployed at address {:?}",
+                contract_address
+            ))),
+        }
+    }
+
+    /// Sets the registry from which classes which aren't declared in the
+    /// state are loaded.
+    #[must_use]
+    pub fn with_class_registry(mut self, class_registry: SharedClassRegistry) -> Self {
+        self.class_registry = Some(class_registry);
+        self
+    }
+
+    /// Returns the class registry of the state, if any.
+    pub const fn class_registry(&self) -> Option<&SharedClassRegistry> {
+        self.class_registry.as_ref()
+    }
+
+    /// Sets the metrics in which the storage reads and writes of the
+    /// executed transactions are recorded.
+    #[must_use]
+    pub fn with_storage_metrics(mut self, storage_metrics: SharedStorageMetrics) -> Self {
+        self.storage_metrics = Some(storage_metrics);
+        self
+    }
+
+    /// Returns the storage metrics of the state, if any.
+    pub const fn storage_metrics(&self) -> Option<&SharedStorageMetrics> {
+        self.storage_metrics.as_ref()
+    }
+}
+
+/// Counts and size statistics of the state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StateStatistics {
+    /// Number of declared classes.
+    pub classes: usize,
+    /// Number of declared Cairo 0 classes.
+    pub cairo_0_classes: usize,
+    /// Number of declared Cairo 1 classes.
+    pub cairo_1_classes: usize,
+    /// Number of compiled class hashes.
+    pub compiled_class_hashes: usize,
+    /// Number of deployed contracts.
+    pub contracts: usize,
+    /// Number of contracts with a nonce.
+    pub nonces: usize,
+    /// Number of storage slots, over all contracts.
+    pub storage_slots: usize,
+    /// Number of contracts with at least one storage slot.
+    pub contracts_with_storage: usize,
+    /// Contract with the most storage slots, along with its number of slots.
+    pub largest_storage: Option<(ContractAddress, usize)>,
+}
+
+/// Read-only inspection of the state.
+impl<B: MapBackend> GenericState<B> {
+    /// Returns an iterator over the deployed contracts and their class hash.
+    pub fn contracts(&self) -> impl Iterator<Item = (&ContractAddress, &ClassHash)> {
+        self.contracts.iter()
+    }
+
+    /// Returns an iterator over the declared classes.
+    pub fn classes(&self) -> impl Iterator<Item = (&ClassHash, &ContractClass)> {
+        self.classes.iter()
+    }
+
+    /// Returns an iterator over the compiled class hashes.
+    pub fn compiled_class_hashes(&self) -> impl Iterator<Item = (&ClassHash, &CompiledClassHash)> {
+        self.compiled_class_hashes.iter()
+    }
+
+    /// Returns an iterator over the nonces.
+    pub fn nonces(&self) -> impl Iterator<Item = (&ContractAddress, &Nonce)> {
+        self.nonces.iter()
+    }
+
+    /// Returns an iterator over all the storage slots.
+    pub fn storage(&self) -> impl Iterator<Item = (&ContractStorageKey, &StarkFelt)> {
+        self.storage.iter()
+    }
+
+    /// Returns an iterator over the storage slots of the contract.
+    pub fn contract_storage<'a>(
+        &'a self,
+        contract_address: &'a ContractAddress,
+    ) -> impl Iterator<Item = (&'a StorageKey, &'a StarkFelt)> {
+        self.storage
+            .iter()
+            .filter(move |((address, _), _)| address == contract_address)
+            .map(|((_, key), value)| (key, value))
+    }
+
+    /// Returns the class hash of the contract, if deployed.
+    pub fn class_hash_of(&self, contract_address: &ContractAddress) -> Option<&ClassHash> {
+        self.contracts.get(contract_address)
+    }
+
+    /// Returns the declared class, if any.
+    pub fn class(&self, class_hash: &ClassHash) -> Option<&ContractClass> {
+        self.classes.get(class_hash)
+    }
+
+    /// Returns the compiled class hash of the class, if any.
+    pub fn compiled_class_hash_of(&self, class_hash: &ClassHash) -> Option<&CompiledClassHash> {
+        self.compiled_class_hashes.get(class_hash)
+    }
+
+    /// Returns the nonce of the contract, if set.
+    pub fn nonce_of(&self, contract_address: &ContractAddress) -> Option<&Nonce> {
+        self.nonces.get(contract_address)
+    }
+
+    /// Returns the storage value at the key for the contract, if set.
+    pub fn storage_of(
+        &self,
+        contract_address: &ContractAddress,
+        key: &StorageKey,
+    ) -> Option<&StarkFelt> {
+        self.storage.get(&(*contract_address, *key))
+    }
+
+    /// Returns true if a contract is deployed at the address.
+    pub fn is_deployed(&self, contract_address: &ContractAddress) -> bool {
+        self.contracts.contains_key(contract_address)
+    }
+
+    /// Returns true if the class is declared, either in the state or in the
+    /// class registry.
+    pub fn is_declared(&self, class_hash: &ClassHash) -> bool {
+        self.classes.contains_key(class_hash)
+            || self
+                .class_registry
+                .as_ref()
+                .map_or(false, |registry| registry.lock().contains(class_hash))
+    }
+
+    /// Returns the number of storage slots of each contract with storage.
+    pub fn storage_sizes(&self) -> B::Map<ContractAddress, usize> {
+        let mut sizes = <B::Map<ContractAddress, usize>>::default();
+        for ((address, _), _) in self.storage.iter() {
+            let size = sizes.get(address).copied().unwrap_or_default();
+            sizes.insert(*address, size + 1);
+        }
+        sizes
+    }
+
+    /// Returns counts and size statistics of the state.
+    pub fn statistics(&self) -> StateStatistics {
+        let cairo_0_classes = self
+            .classes
+            .iter()
+            .filter(|(_, class)| matches!(class, ContractClass::V0(_)))
+            .count();
+        let storage_sizes = self.storage_sizes();
+        let largest_storage = storage_sizes
+            .iter()
+            .max_by_key(|(_, size)| **size)
+            .map(|(address, size)| (*address, *size));
+
+        StateStatistics {
+            classes: self.classes.len(),
+            cairo_0_classes,
+            cairo_1_classes: self.classes.len() - cairo_0_classes,
+            compiled_class_hashes: self.compiled_class_hashes.len(),
+            contracts: self.contracts.len(),
+            nonces: self.nonces.len(),
+            storage_slots: self.storage.len(),
+            contracts_with_storage: storage_sizes.len(),
+            largest_storage,
+        }
+    }
+}
+
+/// Inconsistency of the state, usually caused by a setup mistake, which
+/// would otherwise surface as a confusing error during execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A contract is deployed with a class which isn't declared.
+    UndeclaredClass {
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+    },
+    /// A Cairo 1 class is declared without its compiled class hash.
+    MissingCompiledClassHash { class_hash: ClassHash },
+    /// Storage is set at an address where no contract is deployed.
+    StorageWithoutContract {
+        contract_address: ContractAddress,
+        slots: usize,
+    },
+    /// A nonce is set at an address where no contract is deployed.
+    NonceWithoutContract {
+        contract_address: ContractAddress,
+        nonce: Nonce,
+    },
+}
+
+impl Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndeclaredClass {
+                contract_address,
+                class_hash,
+            } => write!(
+                f,
+                "contract {} is deployed with undeclared class {}",
+                contract_address.0.key(),
+                class_hash.0
+            ),
+            Self::MissingCompiledClassHash { class_hash } => write!(
+                f,
+                "Cairo 1 class {} has no compiled class hash",
+                class_hash.0
+            ),
+            Self::StorageWithoutContract {
+                contract_address,
+                slots,
+            } => write!(
+                f,
+                "{} storage slots are set at {}, where no contract is deployed",
+                slots,
+                contract_address.0.key()
+            ),
+            Self::NonceWithoutContract {
+                contract_address,
+                nonce,
+            } => write!(
+                f,
+                "nonce {} is set at {}, where no contract is deployed",
+                nonce.0,
+                contract_address.0.key()
+            ),
+        }
+    }
+}
+
+/// Report of the invariant violations of the state, sorted by kind then by
+/// address or class hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InvariantReport {
+    pub violations: Vec<InvariantViolation>,
+}
+
+impl InvariantReport {
+    /// Returns true if the state holds all invariants.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl Display for InvariantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "no invariant violation");
+        }
+        for violation in &self.violations {
+            writeln!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: MapBackend> GenericState<B> {
+    /// Checks the consistency of the state:
+    /// - deployed contracts have a declared class, in the state or in the
+    ///   class registry;
+    /// - declared Cairo 1 classes have a compiled class hash;
+    /// - storage and nonces are only set at addresses where a contract is
+    ///   deployed.
+    ///
+    /// Note that setting the balance of an account on a fee token which isn't
+    /// deployed is reported as storage without contract.
+    pub fn check_invariants(&self) -> InvariantReport {
+        let mut violations = Vec::new();
+
+        let mut undeclared: Vec<_> = self
+            .contracts
+            .iter()
+            .filter(|(_, class_hash)| !self.is_declared(class_hash))
+            .map(|(contract_address, class_hash)| (*contract_address, *class_hash))
+            .collect();
+        undeclared.sort();
+        violations.extend(
+            undeclared
+                .into_iter()
+                .map(
+                    |(contract_address, class_hash)| InvariantViolation::UndeclaredClass {
+                        contract_address,
+                        class_hash,
+                    },
+                ),
+        );
+
+        let mut missing_compiled_class_hashes: Vec<_> = self
+            .classes
+            .iter()
+            .filter(|(class_hash, class)| {
+                matches!(class, ContractClass::V1(_))
+                    && !self.compiled_class_hashes.contains_key(class_hash)
+            })
+            .map(|(class_hash, _)| *class_hash)
+            .collect();
+        missing_compiled_class_hashes.sort();
+        violations.extend(
+            missing_compiled_class_hashes
+                .into_iter()
+                .map(|class_hash| InvariantViolation::MissingCompiledClassHash { class_hash }),
+        );
+
+        let mut orphan_storage: Vec<_> = self
+            .storage_sizes()
+            .into_iter()
+            .filter(|(contract_address, _)| !self.is_deployed(contract_address))
+            .collect();
+        orphan_storage.sort();
+        violations.extend(orphan_storage.into_iter().map(|(contract_address, slots)| {
+            InvariantViolation::StorageWithoutContract {
+                contract_address,
+                slots,
+            }
+        }));
+
+        let mut orphan_nonces: Vec<_> = self
+            .nonces
+            .iter()
+            .filter(|(contract_address, _)| !self.is_deployed(contract_address))
+            .map(|(contract_address, nonce)| (*contract_address, *nonce))
+            .collect();
+        orphan_nonces.sort();
+        violations.extend(orphan_nonces.into_iter().map(|(contract_address, nonce)| {
+            InvariantViolation::NonceWithoutContract {
+                contract_address,
+                nonce,
+            }
+        }));
+
+        InvariantReport { violations }
+    }
 }
 
-impl Committer<State> for &mut State {}
+/// Commits the changes of an executed transaction, recording its storage
+/// writes in the storage metrics. Writes made directly on the state, such as
+/// the setup helpers of the sequencer, aren't recorded.
+impl<B: MapBackend> Committer<GenericState<B>> for &mut GenericState<B> {
+    fn commit(cached_state: &mut CachedState<&mut GenericState<B>>) -> StateResult<()> {
+        let diff = cached_state.to_state_diff();
+        let state = &mut cached_state.state;
+        if let Some(metrics) = &state.storage_metrics {
+            let mut metrics = metrics.lock();
+            for (address, storage_updates) in &diff.storage_updates {
+                for key in storage_updates.keys() {
+                    let slot = (*address, *key);
+                    metrics.record_write(slot, !state.storage.contains_key(&slot));
+                }
+            }
+        }
+        apply_state_diff(state, diff)
+    }
+}
 
 /// State implementation for the sequencer. We use a mutable reference to the state
 /// because this is what will be available during the implementation of the execution.
-impl BlockifierState for &mut State {
+impl<B: MapBackend> BlockifierState for &mut GenericState<B> {
     fn set_storage_at(
         &mut self,
         contract_address: ContractAddress,
@@ -141,66 +559,161 @@ impl BlockifierState for &mut State {
     }
 }
 
-impl BlockifierStateReader for &mut State {
+/// Reads of the state, shared by the blockifier readers of the state and of
+/// its read-only view.
+impl<B: MapBackend> GenericState<B> {
+    fn read_storage(&self, contract_address: ContractAddress, key: StorageKey) -> StarkFelt {
+        self.storage
+            .get(&(contract_address, key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn read_nonce(&self, contract_address: ContractAddress) -> Nonce {
+        self.nonces
+            .get(&contract_address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn read_class_hash(&self, contract_address: ContractAddress) -> ClassHash {
+        self.contracts
+            .get(&contract_address)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn read_compiled_contract_class(&self, class_hash: &ClassHash) -> StateResult<ContractClass> {
+        if let Some(class) = self.classes.get(class_hash) {
+            return Ok(class.clone());
+        }
+        let class = match &self.class_registry {
+            Some(registry) => registry
+                .lock()
+                .get(class_hash)
+                .map_err(|error| StateError::StateReadError(error.to_string()))?,
+            None => None,
+        };
+        class.ok_or_else(|| StateError::UndeclaredClassHash(*class_hash))
+    }
+
+    fn read_compiled_class_hash(&self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        if let Some(compiled_class_hash) = self.compiled_class_hashes.get(&class_hash) {
+            return Ok(*compiled_class_hash);
+        }
+        let compiled_class_hash = match &self.class_registry {
+            Some(registry) => registry
+                .lock()
+                .compiled_class_hash(&class_hash)
+                .map_err(|error| StateError::StateReadError(error.to_string()))?,
+            None => None,
+        };
+        compiled_class_hash.ok_or_else(|| StateError::UndeclaredClassHash(class_hash))
+    }
+
+    /// Returns a read-only view of the state, on which transactions can be
+    /// simulated through a blockifier `CachedState` without exclusive access
+    /// to the state.
+    pub const fn view(&self) -> StateView<'_, B> {
+        StateView(self)
+    }
+}
+
+impl<B: MapBackend> BlockifierStateReader for &mut GenericState<B> {
     fn get_storage_at(
         &mut self,
         contract_address: ContractAddress,
         key: StorageKey,
     ) -> StateResult<StarkFelt> {
-        Ok(self
-            .storage
-            .get(&(contract_address, key))
-            .copied()
-            .unwrap_or_default())
+        if let Some(metrics) = &self.storage_metrics {
+            metrics.lock().record_read((contract_address, key));
+        }
+        Ok(self.read_storage(contract_address, key))
     }
 
     fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
-        Ok(self
-            .nonces
-            .get(&contract_address)
-            .copied()
-            .unwrap_or_default())
+        Ok(self.read_nonce(contract_address))
     }
 
     fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
-        Ok(self
-            .contracts
-            .get(&contract_address)
-            .copied()
-            .unwrap_or_default())
+        Ok(self.read_class_hash(contract_address))
     }
 
     /// # Errors
     ///
-    /// If the compiled class is not declared.
+    /// If the compiled class is not declared or fails to load from the
+    /// class registry.
     fn get_compiled_contract_class(
         &mut self,
         class_hash: &ClassHash,
     ) -> StateResult<ContractClass> {
-        self.classes
-            .get(class_hash)
-            .cloned()
-            .ok_or_else(|| StateError::UndeclaredClassHash(*class_hash))
+        self.read_compiled_contract_class(class_hash)
     }
 
     /// # Errors
     ///
-    /// If the compiled class hash is not declared.
+    /// If the compiled class hash is not declared or fails to load from the
+    /// class registry.
     fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
-        self.compiled_class_hashes
-            .get(&class_hash)
-            .copied()
-            .ok_or_else(|| StateError::UndeclaredClassHash(class_hash))
+        self.read_compiled_class_hash(class_hash)
+    }
+}
+
+/// Read-only view of the state. Writes of the execution on top of the view
+/// are kept in the blockifier `CachedState` wrapping it, and never reach the
+/// state. Reads through the view aren't recorded in the storage metrics.
+#[derive(Clone, Copy, Debug)]
+pub struct StateView<'a, B: MapBackend>(&'a GenericState<B>);
+
+impl<B: MapBackend> BlockifierStateReader for StateView<'_, B> {
+    fn get_storage_at(
+        &mut self,
+        contract_address: ContractAddress,
+        key: StorageKey,
+    ) -> StateResult<StarkFelt> {
+        Ok(self.0.read_storage(contract_address, key))
+    }
+
+    fn get_nonce_at(&mut self, contract_address: ContractAddress) -> StateResult<Nonce> {
+        Ok(self.0.read_nonce(contract_address))
+    }
+
+    fn get_class_hash_at(&mut self, contract_address: ContractAddress) -> StateResult<ClassHash> {
+        Ok(self.0.read_class_hash(contract_address))
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class is not declared or fails to load from the
+    /// class registry.
+    fn get_compiled_contract_class(
+        &mut self,
+        class_hash: &ClassHash,
+    ) -> StateResult<ContractClass> {
+        self.0.read_compiled_contract_class(class_hash)
+    }
+
+    /// # Errors
+    ///
+    /// If the compiled class hash is not declared or fails to load from the
+    /// class registry.
+    fn get_compiled_class_hash(&mut self, class_hash: ClassHash) -> StateResult<CompiledClassHash> {
+        self.0.read_compiled_class_hash(class_hash)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use blockifier::execution::contract_class::ContractClassV0;
+    use blockifier::execution::contract_class::{ContractClassV0, ContractClassV1};
+    use blockifier::state::cached_state::GlobalContractCache;
 
+    use crate::class_registry::ClassRegistry;
     use crate::constants::test_constants::{
-        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_CONTRACT,
+        ONE_CLASS_HASH, ONE_COMPILED_CLASS_HASH, ONE_FELT, ONE_PATRICIA, TEST_ACCOUNT,
+        TEST_CONTRACT, TWO_CLASS_HASH, TWO_FELT, TWO_PATRICIA,
     };
+    use crate::metrics::StorageAccess;
+    use crate::state::backend::{BTreeBackend, SipHashBackend};
 
     use super::*;
 
@@ -295,6 +808,131 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_map_backends() {
+        // Given
+        let mut fx_state = State::default();
+        let mut sip_state = GenericState::<SipHashBackend>::default();
+        let mut btree_state = GenericState::<BTreeBackend>::default();
+        for (key, value) in [(*TWO_PATRICIA, *ONE_FELT), (*ONE_PATRICIA, *TWO_FELT)] {
+            (&mut fx_state).set_storage_at(*TEST_CONTRACT, StorageKey(key), value);
+            (&mut sip_state).set_storage_at(*TEST_CONTRACT, StorageKey(key), value);
+            (&mut btree_state).set_storage_at(*TEST_CONTRACT, StorageKey(key), value);
+        }
+
+        // When
+        let keys: Vec<_> = btree_state
+            .contract_storage(&TEST_CONTRACT)
+            .map(|(key, _)| *key)
+            .collect();
+        let from_sip: State = SerializableState::from(sip_state.clone())
+            .into_backend()
+            .into();
+        let from_btree: State = SerializableState::from(btree_state).into_backend().into();
+
+        // Then
+        assert_eq!(
+            keys,
+            vec![StorageKey(*ONE_PATRICIA), StorageKey(*TWO_PATRICIA)]
+        );
+        assert_eq!(
+            (&mut sip_state)
+                .get_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA))
+                .unwrap(),
+            *TWO_FELT
+        );
+        assert_eq!(from_sip, fx_state);
+        assert_eq!(from_btree, fx_state);
+    }
+
+    #[test]
+    fn test_inspect_state() {
+        // Given
+        let mut state = State::default();
+        (&mut state)
+            .set_contract_class(
+                &ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *ONE_FELT);
+        (&mut state).set_storage_at(*TEST_ACCOUNT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+
+        // When
+        let contracts: Vec<_> = state.contracts().collect();
+        let storage: Vec<_> = state.contract_storage(&TEST_CONTRACT).collect();
+        let statistics = state.statistics();
+
+        // Then
+        assert_eq!(contracts, vec![(&*TEST_CONTRACT, &*ONE_CLASS_HASH)]);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(
+            state.storage_of(&TEST_ACCOUNT, &StorageKey(*ONE_PATRICIA)),
+            Some(&*ONE_FELT)
+        );
+        assert!(state.is_deployed(&TEST_CONTRACT));
+        assert!(!state.is_deployed(&TEST_ACCOUNT));
+        assert_eq!(
+            statistics,
+            StateStatistics {
+                classes: 1,
+                cairo_0_classes: 1,
+                cairo_1_classes: 0,
+                compiled_class_hashes: 0,
+                contracts: 1,
+                nonces: 1,
+                storage_slots: 3,
+                contracts_with_storage: 2,
+                largest_storage: Some((*TEST_CONTRACT, 2)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_replace_class() {
+        // Given
+        let mut state = State::default();
+        (&mut state)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+
+        // When
+        state
+            .replace_class(*TEST_CONTRACT, *TWO_CLASS_HASH)
+            .unwrap();
+
+        // Then
+        let expected = *TWO_CLASS_HASH;
+        let actual = (&mut state).get_class_hash_at(*TEST_CONTRACT).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    #[should_panic(expected = "UndeclaredClassHash")]
+    fn test_replace_class_undeclared() {
+        // Given
+        let mut state = State::default();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+
+        // When
+        state
+            .replace_class(*TEST_CONTRACT, *TWO_CLASS_HASH)
+            .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "UndeclaredClassHash")]
     fn test_uninitialized_compiled_class_hash() {
@@ -304,4 +942,125 @@ mod tests {
         // When
         state.get_compiled_class_hash(*ONE_CLASS_HASH).unwrap();
     }
+
+    #[test]
+    fn test_storage_metrics() {
+        // Given
+        let metrics = SharedStorageMetrics::new();
+        let mut state = State::default().with_storage_metrics(metrics.clone());
+
+        (&mut state).set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+
+        // When
+        let mut cached_state = CachedState::new(&mut state, GlobalContractCache::default());
+        cached_state.set_storage_at(*TEST_CONTRACT, StorageKey(*ONE_PATRICIA), *TWO_FELT);
+        cached_state.set_storage_at(*TEST_CONTRACT, StorageKey(*TWO_PATRICIA), *ONE_FELT);
+        cached_state
+            .get_storage_at(*TEST_ACCOUNT, StorageKey(*TWO_PATRICIA))
+            .unwrap();
+        cached_state
+            .get_storage_at(*TEST_ACCOUNT, StorageKey(*TWO_PATRICIA))
+            .unwrap();
+        <&mut State>::commit(&mut cached_state).unwrap();
+
+        // Then
+        // The direct write of the setup isn't recorded, only the committed
+        // writes and the first read of the transaction are.
+        let recorded = metrics.take();
+        assert_eq!(recorded.new_slots(), 1);
+        assert_eq!(recorded.total().writes, 2);
+        assert_eq!(
+            recorded.contracts().get(&*TEST_ACCOUNT),
+            Some(&StorageAccess {
+                reads: 1,
+                writes: 0
+            })
+        );
+        assert!(metrics.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        // Given
+        let mut state = State::default();
+        let account_class = include_str!("./test_data/cairo_1/compiled_classes/account.json");
+        (&mut state)
+            .set_contract_class(
+                &TWO_CLASS_HASH,
+                ContractClass::V1(ContractClassV1::try_from_json_string(account_class).unwrap()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_CONTRACT, *ONE_CLASS_HASH)
+            .unwrap();
+        (&mut state).set_storage_at(*TEST_ACCOUNT, StorageKey(*ONE_PATRICIA), *ONE_FELT);
+        state.set_nonce(*TEST_ACCOUNT, Nonce(*ONE_FELT));
+
+        // When
+        let report = state.check_invariants();
+
+        // Then
+        assert_eq!(
+            report.violations,
+            vec![
+                InvariantViolation::UndeclaredClass {
+                    contract_address: *TEST_CONTRACT,
+                    class_hash: *ONE_CLASS_HASH,
+                },
+                InvariantViolation::MissingCompiledClassHash {
+                    class_hash: *TWO_CLASS_HASH,
+                },
+                InvariantViolation::StorageWithoutContract {
+                    contract_address: *TEST_ACCOUNT,
+                    slots: 1,
+                },
+                InvariantViolation::NonceWithoutContract {
+                    contract_address: *TEST_ACCOUNT,
+                    nonce: Nonce(*ONE_FELT),
+                },
+            ]
+        );
+
+        // When
+        (&mut state)
+            .set_contract_class(
+                &ONE_CLASS_HASH,
+                ContractClass::V0(ContractClassV0::default()),
+            )
+            .unwrap();
+        (&mut state)
+            .set_compiled_class_hash(*TWO_CLASS_HASH, *ONE_COMPILED_CLASS_HASH)
+            .unwrap();
+        (&mut state)
+            .set_class_hash_at(*TEST_ACCOUNT, *TWO_CLASS_HASH)
+            .unwrap();
+
+        // Then
+        assert!(state.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_class_registry() {
+        // Given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("counter.json");
+        std::fs::write(
+            &path,
+            include_str!("./test_data/cairo_0/compiled_classes/counter.json"),
+        )
+        .unwrap();
+        let mut registry = ClassRegistry::new(1);
+        registry.register(*TWO_CLASS_HASH, path);
+        let mut state = State::default().with_class_registry(SharedClassRegistry::new(registry));
+
+        // When
+        let class = (&mut state).get_compiled_contract_class(&TWO_CLASS_HASH);
+
+        // Then
+        assert!(matches!(class, Ok(ContractClass::V0(_))));
+        assert!(state.is_declared(&TWO_CLASS_HASH));
+        assert_eq!(state.statistics().classes, 0);
+        let reloaded: State = SerializableState::from(state.clone()).into();
+        assert_eq!(reloaded, state);
+    }
 }
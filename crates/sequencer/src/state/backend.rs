@@ -0,0 +1,185 @@
+use std::{
+    collections::{btree_map, hash_map, BTreeMap, HashMap},
+    fmt::Debug,
+    hash::{BuildHasher, Hash},
+};
+
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Bounds of the keys of the state maps.
+pub trait StateKey:
+    Clone + Debug + Eq + Ord + Hash + Send + Sync + Serialize + DeserializeOwned
+{
+}
+
+impl<T> StateKey for T where
+    T: Clone + Debug + Eq + Ord + Hash + Send + Sync + Serialize + DeserializeOwned
+{
+}
+
+/// Bounds of the values of the state maps.
+pub trait StateValue: Clone + Debug + Eq + Send + Sync + Serialize + DeserializeOwned {}
+
+impl<T> StateValue for T where T: Clone + Debug + Eq + Send + Sync + Serialize + DeserializeOwned {}
+
+/// Map operations used by the state, implemented for the hash maps and
+/// for `BTreeMap`.
+pub trait StateMap<K, V>:
+    Clone
+    + Debug
+    + Default
+    + Eq
+    + Send
+    + Sync
+    + Serialize
+    + DeserializeOwned
+    + FromIterator<(K, V)>
+    + IntoIterator<Item = (K, V)>
+    + Extend<(K, V)>
+{
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    /// Creates an empty map, preallocated for `capacity` entries when the map
+    /// supports it.
+    fn with_capacity(capacity: usize) -> Self;
+
+    fn get(&self, key: &K) -> Option<&V>;
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V>;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    fn contains_key(&self, key: &K) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+impl<K, V, S> StateMap<K, V> for HashMap<K, V, S>
+where
+    K: StateKey,
+    V: StateValue,
+    S: BuildHasher + Clone + Default + Send + Sync,
+{
+    type Iter<'a> = hash_map::Iter<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    fn with_capacity(capacity: usize) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, S::default())
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        HashMap::contains_key(self, key)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        HashMap::iter(self)
+    }
+}
+
+impl<K, V> StateMap<K, V> for BTreeMap<K, V>
+where
+    K: StateKey,
+    V: StateValue,
+{
+    type Iter<'a> = btree_map::Iter<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    fn with_capacity(_capacity: usize) -> Self {
+        BTreeMap::new()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        BTreeMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        BTreeMap::contains_key(self, key)
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeMap::iter(self)
+    }
+}
+
+/// Map backend of the state, selecting the map type used for every field of
+/// the state.
+pub trait MapBackend:
+    Clone + Copy + Debug + Default + PartialEq + Eq + Send + Sync + 'static
+{
+    type Map<K: StateKey, V: StateValue>: StateMap<K, V>;
+}
+
+/// Hash maps with the non cryptographic hash function of rustc. This is the
+/// fastest backend, but it must not be used with untrusted input, since
+/// colliding keys are easy to forge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FxHashBackend;
+
+impl MapBackend for FxHashBackend {
+    type Map<K: StateKey, V: StateValue> = FxHashMap<K, V>;
+}
+
+/// Hash maps with the randomly keyed SipHash function of the standard
+/// library, resistant to hash flooding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SipHashBackend;
+
+impl MapBackend for SipHashBackend {
+    type Map<K: StateKey, V: StateValue> = HashMap<K, V>;
+}
+
+/// Ordered maps: iterating over the state follows the order of the keys,
+/// which makes it deterministic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BTreeBackend;
+
+impl MapBackend for BTreeBackend {
+    type Map<K: StateKey, V: StateValue> = BTreeMap<K, V>;
+}
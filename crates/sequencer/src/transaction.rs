@@ -1,19 +1,35 @@
+use std::io::Read;
 use std::sync::Arc;
 
-use blockifier::transaction::transactions::InvokeTransaction as BlockifierInvokeTransaction;
+use blockifier::execution::contract_class::{ContractClass, ContractClassV0};
+use blockifier::transaction::transactions::{
+    DeclareTransaction as BlockifierDeclareTransaction,
+    DeployAccountTransaction as BlockifierDeployAccountTransaction,
+    InvokeTransaction as BlockifierInvokeTransaction,
+};
 use blockifier::transaction::{
     account_transaction::AccountTransaction,
     transaction_execution::Transaction as ExecutionTransaction,
 };
+use cairo_lang_starknet::contract_class::ContractClass as SierraContractClass;
+use flate2::read::GzDecoder;
 use starknet::core::crypto::compute_hash_on_elements;
-use starknet::core::types::{BroadcastedTransaction, FieldElement};
-use starknet_api::core::{ContractAddress, Nonce, PatriciaKey};
+use starknet::core::types::contract::legacy::LegacyContractClass;
+use starknet::core::types::{
+    BroadcastedDeclareTransaction, BroadcastedDeployAccountTransaction, BroadcastedTransaction,
+    CompressedLegacyContractClass, FieldElement, FlattenedSierraClass,
+};
+use starknet::core::utils::get_contract_address;
+use starknet_api::core::{ClassHash, CompiledClassHash, ContractAddress, Nonce, PatriciaKey};
 use starknet_api::hash::{StarkFelt, StarkHash};
-use starknet_api::transaction::InvokeTransaction;
 use starknet_api::transaction::{
-    Calldata, Fee, InvokeTransactionV1, TransactionHash, TransactionSignature,
+    Calldata, ContractAddressSalt, DeclareTransaction, DeclareTransactionV0V1,
+    DeclareTransactionV2, DeployAccountTransaction, Fee, InvokeTransaction, InvokeTransactionV1,
+    TransactionHash, TransactionSignature, TransactionVersion,
 };
 
+use crate::sierra::{compile_sierra_class_with_hash, SierraCompilationError};
+
 /// Wrapper around a Starknet-rs transaction.
 /// Allows for conversion from a Starknet-rs
 /// transaction to a Blockifier-rs transaction.
@@ -70,13 +86,193 @@ impl BroadcastedTransactionWrapper {
                     ))),
                 }),
             )),
-            // TODO: Add support for other transaction types.
-            _ => Err(eyre::eyre!("Unsupported transaction type")),
+            BroadcastedTransaction::Declare(declare) => {
+                declare_to_execution_transaction(declare, chain_id)
+            }
+            BroadcastedTransaction::DeployAccount(deploy_account) => {
+                deploy_account_to_execution_transaction(deploy_account, chain_id)
+            }
         }
     }
 }
 
-fn compute_transaction_hash(
+fn declare_to_execution_transaction(
+    declare: BroadcastedDeclareTransaction,
+    chain_id: FieldElement,
+) -> Result<ExecutionTransaction, eyre::Error> {
+    let (tx, tx_hash, contract_class) = match declare {
+        BroadcastedDeclareTransaction::V1(declare) => {
+            let (contract_class, class_hash) =
+                legacy_to_contract_class_with_hash(&declare.contract_class)?;
+            let tx_hash = compute_declare_transaction_hash(
+                declare.sender_address,
+                class_hash,
+                declare.max_fee,
+                chain_id,
+                declare.nonce,
+                None,
+            );
+            let tx = DeclareTransaction::V1(DeclareTransactionV0V1 {
+                max_fee: Fee(declare.max_fee.try_into()?),
+                signature: to_signature(&declare.signature),
+                nonce: Nonce(declare.nonce.into()),
+                class_hash: ClassHash(class_hash.into()),
+                sender_address: to_contract_address(declare.sender_address)?,
+            });
+            (tx, tx_hash, contract_class)
+        }
+        BroadcastedDeclareTransaction::V2(declare) => {
+            let class_hash = declare.contract_class.class_hash();
+            let (contract_class, compiled_class_hash) =
+                flattened_sierra_to_contract_class(&declare.contract_class)?;
+            let declared = CompiledClassHash(declare.compiled_class_hash.into());
+            if compiled_class_hash != declared {
+                return Err(SierraCompilationError::CompiledClassHashMismatch {
+                    declared,
+                    computed: compiled_class_hash,
+                }
+                .into());
+            }
+            let tx_hash = compute_declare_transaction_hash(
+                declare.sender_address,
+                class_hash,
+                declare.max_fee,
+                chain_id,
+                declare.nonce,
+                Some(declare.compiled_class_hash),
+            );
+            let tx = DeclareTransaction::V2(DeclareTransactionV2 {
+                max_fee: Fee(declare.max_fee.try_into()?),
+                signature: to_signature(&declare.signature),
+                nonce: Nonce(declare.nonce.into()),
+                class_hash: ClassHash(class_hash.into()),
+                compiled_class_hash: declared,
+                sender_address: to_contract_address(declare.sender_address)?,
+            });
+            (tx, tx_hash, contract_class)
+        }
+    };
+
+    Ok(ExecutionTransaction::AccountTransaction(
+        AccountTransaction::Declare(BlockifierDeclareTransaction::new(
+            tx,
+            TransactionHash(tx_hash.into()),
+            contract_class,
+        )?),
+    ))
+}
+
+fn deploy_account_to_execution_transaction(
+    deploy_account: BroadcastedDeployAccountTransaction,
+    chain_id: FieldElement,
+) -> Result<ExecutionTransaction, eyre::Error> {
+    let contract_address = get_contract_address(
+        deploy_account.contract_address_salt,
+        deploy_account.class_hash,
+        &deploy_account.constructor_calldata,
+        FieldElement::ZERO,
+    );
+    let tx_hash = compute_deploy_account_transaction_hash(
+        contract_address,
+        deploy_account.class_hash,
+        deploy_account.contract_address_salt,
+        &deploy_account.constructor_calldata,
+        deploy_account.max_fee,
+        chain_id,
+        deploy_account.nonce,
+    );
+
+    Ok(ExecutionTransaction::AccountTransaction(
+        AccountTransaction::DeployAccount(BlockifierDeployAccountTransaction {
+            tx: DeployAccountTransaction {
+                max_fee: Fee(deploy_account.max_fee.try_into()?),
+                version: TransactionVersion(StarkFelt::from(1u8)),
+                signature: to_signature(&deploy_account.signature),
+                nonce: Nonce(deploy_account.nonce.into()),
+                class_hash: ClassHash(deploy_account.class_hash.into()),
+                contract_address_salt: ContractAddressSalt(
+                    deploy_account.contract_address_salt.into(),
+                ),
+                constructor_calldata: Calldata(Arc::new(
+                    deploy_account
+                        .constructor_calldata
+                        .iter()
+                        .map(|x| Into::<StarkFelt>::into(*x))
+                        .collect(),
+                )),
+            },
+            tx_hash: TransactionHash(tx_hash.into()),
+            contract_address: to_contract_address(contract_address)?,
+            only_query: false,
+        }),
+    ))
+}
+
+/// Decompresses a Cairo 0 class from its RPC representation, in which the
+/// program is gzip compressed, into the layout of a compiled class file.
+fn decompress_legacy_class(
+    class: &CompressedLegacyContractClass,
+) -> Result<serde_json::Value, eyre::Error> {
+    let mut program = Vec::new();
+    GzDecoder::new(class.program.as_slice()).read_to_end(&mut program)?;
+
+    Ok(serde_json::json!({
+        "program": serde_json::from_slice::<serde_json::Value>(&program)?,
+        "entry_points_by_type": class.entry_points_by_type,
+        "abi": class.abi.clone().unwrap_or_default(),
+    }))
+}
+
+/// Builds a Cairo 0 class from its RPC representation.
+pub(crate) fn legacy_to_contract_class(
+    class: &CompressedLegacyContractClass,
+) -> Result<ContractClass, eyre::Error> {
+    let class: ContractClassV0 = serde_json::from_value(decompress_legacy_class(class)?)?;
+    Ok(ContractClass::V0(class))
+}
+
+/// Builds a Cairo 0 class from its RPC representation, along with its class
+/// hash. The hash is computed on the decompressed class, the compressed one
+/// can't be hashed.
+fn legacy_to_contract_class_with_hash(
+    class: &CompressedLegacyContractClass,
+) -> Result<(ContractClass, FieldElement), eyre::Error> {
+    let class = decompress_legacy_class(class)?;
+    let class_hash = serde_json::from_value::<LegacyContractClass>(class.clone())?.class_hash()?;
+    let class: ContractClassV0 = serde_json::from_value(class)?;
+    Ok((ContractClass::V0(class), class_hash))
+}
+
+/// Compiles a Sierra class from its RPC representation. The ABI is dropped,
+/// since it's only provided as a string and isn't needed for the execution.
+pub(crate) fn flattened_sierra_to_contract_class(
+    class: &FlattenedSierraClass,
+) -> Result<(ContractClass, CompiledClassHash), eyre::Error> {
+    let mut class = serde_json::to_value(class)?;
+    class["abi"] = serde_json::Value::Null;
+    class["sierra_program_debug_info"] = serde_json::Value::Null;
+    let class: SierraContractClass = serde_json::from_value(class)?;
+    Ok(compile_sierra_class_with_hash(class)?)
+}
+
+fn to_signature(signature: &[FieldElement]) -> TransactionSignature {
+    TransactionSignature(
+        signature
+            .iter()
+            .map(|x| Into::<StarkFelt>::into(*x))
+            .collect(),
+    )
+}
+
+fn to_contract_address(address: FieldElement) -> Result<ContractAddress, eyre::Error> {
+    Ok(ContractAddress(TryInto::<PatriciaKey>::try_into(Into::<
+        StarkFelt,
+    >::into(
+        address,
+    ))?))
+}
+
+pub(crate) fn compute_transaction_hash(
     sender_address: FieldElement,
     calldata: &[FieldElement],
     max_fee: FieldElement,
@@ -94,3 +290,261 @@ fn compute_transaction_hash(
         nonce,
     ])
 }
+
+pub(crate) fn compute_declare_transaction_hash(
+    sender_address: FieldElement,
+    class_hash: FieldElement,
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+    compiled_class_hash: Option<FieldElement>,
+) -> FieldElement {
+    let version = if compiled_class_hash.is_some() {
+        FieldElement::TWO
+    } else {
+        FieldElement::ONE
+    };
+    let mut elements = vec![
+        FieldElement::from_byte_slice_be(b"declare").unwrap(),
+        version,
+        sender_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(&[class_hash]),
+        max_fee,
+        chain_id,
+        nonce,
+    ];
+    elements.extend(compiled_class_hash);
+    compute_hash_on_elements(&elements)
+}
+
+pub(crate) fn compute_deploy_account_transaction_hash(
+    contract_address: FieldElement,
+    class_hash: FieldElement,
+    contract_address_salt: FieldElement,
+    constructor_calldata: &[FieldElement],
+    max_fee: FieldElement,
+    chain_id: FieldElement,
+    nonce: FieldElement,
+) -> FieldElement {
+    let mut calldata = vec![class_hash, contract_address_salt];
+    calldata.extend_from_slice(constructor_calldata);
+    compute_hash_on_elements(&[
+        FieldElement::from_byte_slice_be(b"deploy_account").unwrap(),
+        FieldElement::ONE,
+        contract_address,
+        FieldElement::ZERO, // entry_point_selector
+        compute_hash_on_elements(&calldata),
+        max_fee,
+        chain_id,
+        nonce,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet::core::chain_id::MAINNET;
+    use starknet::core::types::{BroadcastedDeclareTransactionV1, BroadcastedDeclareTransactionV2};
+    use starknet::macros::felt;
+
+    use super::*;
+
+    fn counter_contract() -> LegacyContractClass {
+        serde_json::from_str(include_str!(
+            "./test_data/cairo_0/compiled_classes/counter.json"
+        ))
+        .unwrap()
+    }
+
+    fn minimal_contract() -> FlattenedSierraClass {
+        let mut class: serde_json::Value = serde_json::from_str(include_str!(
+            "./test_data/cairo_1/sierra_classes/minimal_contract.json"
+        ))
+        .unwrap();
+        // The ABI of a flattened class is a string.
+        class["abi"] = class["abi"].to_string().into();
+        class
+            .as_object_mut()
+            .unwrap()
+            .remove("sierra_program_debug_info");
+        serde_json::from_value(class).unwrap()
+    }
+
+    fn declare_v2(compiled_class_hash: FieldElement) -> BroadcastedTransaction {
+        BroadcastedTransaction::Declare(BroadcastedDeclareTransaction::V2(
+            BroadcastedDeclareTransactionV2 {
+                contract_class: Arc::new(minimal_contract()),
+                sender_address: felt!("0x1"),
+                compiled_class_hash,
+                max_fee: FieldElement::ZERO,
+                signature: vec![],
+                nonce: FieldElement::ZERO,
+                is_query: false,
+            },
+        ))
+    }
+
+    #[test]
+    fn test_compute_declare_transaction_hash() {
+        // Given
+        // Mainnet declare v1 transaction 0x70fb3ea4...c925.
+        let v1 = (
+            felt!("0x3e2a6434cebce4475cdf8843859f2137259918171eba8d462e3d34c5c4fd110"),
+            felt!("0x4dae654c7b6707667a178729b512d61494fe590ab4accc46923d6409b97e617"),
+            felt!("0x1f04d0693837"),
+            felt!("0x15"),
+            None,
+        );
+        // Mainnet declare v2 transaction 0x7debe525...e062.
+        let v2 = (
+            felt!("0x75341b8090a4257f22dafffe3a4cb882006bd26302720d6a80a1fde154a3430"),
+            felt!("0x4d90a3b52871831b34bc936d9aee304b7205202e649dceef5ee4392659ab33"),
+            felt!("0xb48df232e93750"),
+            felt!("0x1f9"),
+            Some(felt!(
+                "0x3c1296b5f7e6a30bc0167bf30e0700eebb2e9a06228e24cc3ad386502125bcf"
+            )),
+        );
+
+        // When
+        let [v1, v2] = [v1, v2].map(
+            |(sender_address, class_hash, max_fee, nonce, compiled_class_hash)| {
+                compute_declare_transaction_hash(
+                    sender_address,
+                    class_hash,
+                    max_fee,
+                    MAINNET,
+                    nonce,
+                    compiled_class_hash,
+                )
+            },
+        );
+
+        // Then
+        assert_eq!(
+            v1,
+            felt!("0x70fb3ea4b786b752817a32d4835eaba19da495392df90f7f827cd9b60b2c925")
+        );
+        assert_eq!(
+            v2,
+            felt!("0x7debe525c66a929048236c8f6da5903e4f141e5cb5e6cb23e9af33ecaabe062")
+        );
+    }
+
+    #[test]
+    fn test_deploy_account_to_execution_transaction() {
+        // Given
+        // Mainnet deploy account v1 transaction 0x40e7ac7e...c5e4.
+        let salt = felt!("0x54c617a2e91df5344958e0eb2c30c58a1134b3f8e59e88deba60a24f95c0a2c");
+        let deploy_account = BroadcastedDeployAccountTransaction {
+            max_fee: felt!("0x3a23c71d8b9"),
+            signature: vec![],
+            nonce: FieldElement::ZERO,
+            contract_address_salt: salt,
+            constructor_calldata: vec![salt],
+            class_hash: felt!("0x13bfe114fb1cf405bfc3a7f8dbe2d91db146c17521d40dcf57e16d6b59fa8e6"),
+            is_query: false,
+        };
+
+        // When
+        let transaction = BroadcastedTransactionWrapper::new(
+            BroadcastedTransaction::DeployAccount(deploy_account),
+        )
+        .try_into_execution_transaction(MAINNET)
+        .unwrap();
+
+        // Then
+        let ExecutionTransaction::AccountTransaction(AccountTransaction::DeployAccount(
+            transaction,
+        )) = transaction
+        else {
+            panic!("expected a deploy account transaction");
+        };
+        assert_eq!(
+            transaction.tx_hash,
+            TransactionHash(
+                felt!("0x40e7ac7efc374f3d1241c6f991de2ea534d84e8be307420658353527226c5e4").into()
+            )
+        );
+        assert_eq!(
+            transaction.contract_address,
+            to_contract_address(felt!(
+                "0x2aaedc4cc67a5ad0134868689f49b9c8838db640a5c84a13e6cccc828c21aa"
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_declare_v1_to_execution_transaction() {
+        // Given
+        let class = counter_contract();
+        let declare = BroadcastedDeclareTransactionV1 {
+            max_fee: FieldElement::ZERO,
+            signature: vec![],
+            nonce: FieldElement::ZERO,
+            contract_class: Arc::new(class.compress().unwrap()),
+            sender_address: felt!("0x1"),
+            is_query: false,
+        };
+
+        // When
+        let transaction = BroadcastedTransactionWrapper::new(BroadcastedTransaction::Declare(
+            BroadcastedDeclareTransaction::V1(declare),
+        ))
+        .try_into_execution_transaction(MAINNET)
+        .unwrap();
+
+        // Then
+        let ExecutionTransaction::AccountTransaction(AccountTransaction::Declare(transaction)) =
+            transaction
+        else {
+            panic!("expected a declare transaction");
+        };
+        assert_eq!(
+            transaction.tx().class_hash(),
+            ClassHash(class.class_hash().unwrap().into())
+        );
+    }
+
+    #[test]
+    fn test_declare_v2_to_execution_transaction() {
+        // Given
+        let (_, compiled_class_hash) =
+            flattened_sierra_to_contract_class(&minimal_contract()).unwrap();
+        let compiled_class_hash: FieldElement = compiled_class_hash.0.into();
+
+        // When
+        let transaction = BroadcastedTransactionWrapper::new(declare_v2(compiled_class_hash))
+            .try_into_execution_transaction(MAINNET)
+            .unwrap();
+
+        // Then
+        let ExecutionTransaction::AccountTransaction(AccountTransaction::Declare(transaction)) =
+            transaction
+        else {
+            panic!("expected a declare transaction");
+        };
+        assert_eq!(
+            transaction.tx().class_hash(),
+            ClassHash(minimal_contract().class_hash().into())
+        );
+    }
+
+    #[test]
+    fn test_declare_v2_compiled_class_hash_mismatch() {
+        // Given
+        let transaction = declare_v2(felt!("0x1"));
+
+        // When
+        let result =
+            BroadcastedTransactionWrapper::new(transaction).try_into_execution_transaction(MAINNET);
+
+        // Then
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SierraCompilationError>(),
+            Some(SierraCompilationError::CompiledClassHashMismatch { .. })
+        ));
+    }
+}